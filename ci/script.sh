@@ -4,6 +4,8 @@ set -xeo pipefail
 
 cargo test --no-default-features
 cargo test $FEATURES
+cargo test --features zeroize
+cargo test --features secrecy
 
 # cargo test for wasm and cargo check for android and osx targets doesn't
 # need to be repeated on other os and arch.
@@ -0,0 +1,27 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Branch-free byte comparisons. Each function returns a mask that is `0xff`
+//! if the condition holds and `0` otherwise.
+
+/// `a > b`
+#[inline(always)]
+pub(crate) fn gt(a: u8, b: u8) -> u8 {
+    // The high byte of the difference is all ones iff it's negative.
+    ((b as u16).wrapping_sub(a as u16) >> 8) as u8
+}
+
+/// `lo <= a && a <= hi`
+#[inline(always)]
+pub(crate) fn in_range(a: u8, lo: u8, hi: u8) -> u8 {
+    !gt(lo, a) & !gt(a, hi)
+}
+
+/// `a == b`
+#[inline(always)]
+pub(crate) fn eq(a: u8, b: u8) -> u8 {
+    in_range(a, b, b)
+}
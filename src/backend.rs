@@ -0,0 +1,209 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use core::fmt;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// Available implementations of encoding and decoding
+///
+/// See the [crate documentation](crate) for the security properties of each
+/// implementation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Backend {
+    /// 256-bit SIMD, only available on x86 CPUs with AVX2, BMI1, SSE4.2 and
+    /// POPCNT
+    Avx2,
+    /// 128-bit SIMD, only available on x86 CPUs with SSSE3 and SSE4.1
+    Ssse3,
+    /// Branch-free arithmetic on 8 characters at a time within a 64-bit
+    /// integer
+    Swar,
+    /// Branch-free arithmetic on one character at a time
+    Arithmetic,
+    /// Lookups in 64-byte aligned lookup tables
+    LutAlign64,
+}
+
+static ALL: [Backend; 5] = [
+    Backend::Avx2,
+    Backend::Ssse3,
+    Backend::Swar,
+    Backend::Arithmetic,
+    Backend::LutAlign64,
+];
+
+impl Backend {
+    /// Whether this backend can be used on the current CPU.
+    pub fn is_available(self) -> bool {
+        match self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => {
+                is_x86_feature_detected!("avx2")
+                    && is_x86_feature_detected!("bmi1")
+                    && is_x86_feature_detected!("sse4.2")
+                    && is_x86_feature_detected!("popcnt")
+            }
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Ssse3 => {
+                is_x86_feature_detected!("ssse3") && is_x86_feature_detected!("sse4.1")
+            }
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            Backend::Avx2 | Backend::Ssse3 => false,
+            Backend::Swar | Backend::Arithmetic | Backend::LutAlign64 => true,
+        }
+    }
+
+    /// The fastest backend available on the current CPU.
+    pub fn detect() -> Backend {
+        *ALL.iter().find(|b| b.is_available()).unwrap()
+    }
+
+    fn to_u8(self) -> u8 {
+        ALL.iter().position(|&b| b == self).unwrap() as u8 + 1
+    }
+
+    fn from_u8(v: u8) -> Option<Backend> {
+        ALL.get((v as usize).checked_sub(1)?).cloned()
+    }
+}
+
+/// The error returned when selecting a backend that isn't available on the
+/// current CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendUnavailable(pub Backend);
+
+impl fmt::Display for BackendUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BackendUnavailable {}
+
+/// The process-wide backend, 0 if it hasn't been detected yet.
+static BACKEND: AtomicU8 = AtomicU8::new(0);
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static THREAD_BACKEND: core::cell::Cell<Option<Backend>> = const { core::cell::Cell::new(None) };
+}
+
+/// The backend used by encoding and decoding functions on the current
+/// thread.
+///
+/// Unless changed using [`set_backend`] or [`with_backend`], this is
+/// [`Backend::detect()`]. Detection happens only once per process.
+pub fn backend() -> Backend {
+    #[cfg(feature = "std")]
+    {
+        if let Some(backend) = THREAD_BACKEND.with(|b| b.get()) {
+            return backend;
+        }
+    }
+
+    match Backend::from_u8(BACKEND.load(Ordering::Relaxed)) {
+        Some(backend) => backend,
+        None => {
+            let backend = Backend::detect();
+            // Don't overwrite a backend set concurrently by `set_backend`
+            match BACKEND.compare_exchange(0, backend.to_u8(), Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => backend,
+                Err(v) => Backend::from_u8(v).unwrap(),
+            }
+        }
+    }
+}
+
+/// Use `backend` for all subsequent encoding and decoding on all threads.
+///
+/// Encoders and decoders that have already been created keep using the
+/// backend that was selected when they were created.
+pub fn set_backend(backend: Backend) -> Result<(), BackendUnavailable> {
+    if !backend.is_available() {
+        return Err(BackendUnavailable(backend));
+    }
+    BACKEND.store(backend.to_u8(), Ordering::Relaxed);
+    Ok(())
+}
+
+/// Call `f`, using `backend` for all encoding and decoding on the current
+/// thread during the call.
+///
+/// This takes precedence over [`set_backend`].
+#[cfg(feature = "std")]
+pub fn with_backend<R, F: FnOnce() -> R>(backend: Backend, f: F) -> Result<R, BackendUnavailable> {
+    /// Restores the previous backend, even if `f` panics.
+    struct Restore(Option<Backend>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            THREAD_BACKEND.with(|b| b.set(self.0));
+        }
+    }
+
+    if !backend.is_available() {
+        return Err(BackendUnavailable(backend));
+    }
+    let _restore = Restore(THREAD_BACKEND.with(|b| b.replace(Some(backend))));
+    Ok(f())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn all_backends_agree() {
+        use crate::{FromBase64, ToBase64, MIME};
+
+        let input = crate::test_support::rand_base64_size(1000);
+        let data = input.from_base64().unwrap();
+        let encoded = data.to_base64(MIME);
+        for &b in ALL.iter().filter(|b| b.is_available()) {
+            with_backend(b, || {
+                assert_eq!(backend(), b);
+                assert_eq!(input.from_base64().unwrap(), data);
+                assert_eq!(data.to_base64(MIME), encoded);
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn with_backend_restores() {
+        let before = backend();
+        with_backend(Backend::LutAlign64, || {
+            with_backend(Backend::Arithmetic, || {
+                assert_eq!(backend(), Backend::Arithmetic)
+            })
+            .unwrap();
+            assert_eq!(backend(), Backend::LutAlign64);
+        })
+        .unwrap();
+        assert_eq!(backend(), before);
+    }
+
+    #[test]
+    fn roundtrip_u8() {
+        for &b in ALL.iter() {
+            assert_eq!(Backend::from_u8(b.to_u8()), Some(b));
+        }
+        assert_eq!(Backend::from_u8(0), None);
+    }
+
+    #[test]
+    fn display_errors() {
+        println!(
+            "Backend unavailable is {}",
+            BackendUnavailable(Backend::Avx2)
+        );
+    }
+}
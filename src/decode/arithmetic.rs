@@ -0,0 +1,56 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::arithmetic::{eq, in_range};
+
+#[derive(Copy, Clone)]
+pub(super) struct Arithmetic;
+
+impl super::Decoder for Arithmetic {
+    type Block = [u8; 1];
+
+    #[inline]
+    fn decode_block(
+        self,
+        block: &mut Self::Block,
+        config: crate::DecodeConfig,
+    ) -> super::BlockResult {
+        let b = block[0];
+
+        let upper = in_range(b, b'A', b'Z');
+        let lower = in_range(b, b'a', b'z');
+        let digit = in_range(b, b'0', b'9');
+        let c62 = eq(b, b'+') | eq(b, b'-');
+        let c63 = eq(b, b'/') | eq(b, b'_');
+        let space =
+            eq(b, b' ') | eq(b, b'\t') | eq(b, b'\n') | eq(b, 0x0b) | eq(b, 0x0c) | eq(b, b'\r');
+        let pad = eq(b, b'=');
+
+        let value = (upper & b.wrapping_sub(b'A'))
+            | (lower & b.wrapping_sub(b'a' - 26))
+            | (digit & b.wrapping_add(52 - b'0'))
+            | (c62 & 62)
+            | (c63 & 63);
+
+        let rejected = super::is_rejected(b, config);
+        let valid = (upper | lower | digit | c62 | c63) & !rejected;
+        let invalid = !(upper | lower | digit | c62 | c63 | space | pad) | rejected;
+
+        block[0] = value;
+
+        super::BlockResult {
+            out_length: valid & 1,
+            valid: (valid & 1) as u32,
+            invalid: (invalid & 1) as u32,
+            pad: (pad & 1) as u32,
+        }
+    }
+
+    #[inline(always)]
+    fn zero_block() -> Self::Block {
+        [b' '; 1]
+    }
+}
@@ -10,11 +10,12 @@ use core::arch::x86::*;
 use core::arch::x86_64::*;
 
 use crate::avx2::*;
+use crate::misc::Scratch;
 
 /// # Safety
 /// The caller should ensure the requisite CPU features are enabled.
 #[target_feature(enable = "avx2,bmi1,sse4.2,popcnt")]
-unsafe fn decode_avx2(input: __m256i) -> (__m256i, u32, u32) {
+unsafe fn decode_avx2(input: __m256i, config: crate::DecodeConfig) -> (__m256i, u32, u32, u32) {
     // Step 0. Split input bytes into nibbles.
     let higher_nibble = _mm256_and_si256(_mm256_srli_epi16(input, 4), _mm256_set1_epi8(0x0f));
     let lower_nibble = _mm256_and_si256(input, _mm256_set1_epi8(0x0f));
@@ -26,13 +27,13 @@ unsafe fn decode_avx2(input: __m256i) -> (__m256i, u32, u32) {
     // This table contains 128 bits, one bit for each of the lower 128 ASCII
     // characters. A set bit indicates that the character is in the base64
     // character set (the character is valid) or the character is considered
-    // ASCII whitespace. This table is indexed by ASCII low nibble.
+    // ASCII whitespace or padding. This table is indexed by ASCII low nibble.
     #[rustfmt::skip]
     let row_lut = dup_mm_setr_epu8([
         0b1010_1100, 0b1111_1000, 0b1111_1000, 0b1111_1000, 
         0b1111_1000, 0b1111_1000, 0b1111_1000, 0b1111_1000, 
-        0b1111_1000, 0b1111_1001, 0b1111_0001, 0b0101_0100, 
-        0b0101_0001, 0b0101_0101, 0b0101_0000, 0b0111_0100,
+        0b1111_1000, 0b1111_1001, 0b1111_0001, 0b0101_0101, 
+        0b0101_0001, 0b0101_1101, 0b0101_0000, 0b0111_0100,
     ]);
 
     // This table contains column offsets (within a byte) for the table above.
@@ -51,8 +52,31 @@ unsafe fn decode_avx2(input: __m256i) -> (__m256i, u32, u32) {
     let valid = _mm256_and_si256(row, column);
     // Compute invalid character mask
     let non_match = _mm256_cmpeq_epi8(valid, _mm256_setzero_si256());
+    // Compute mask for the special characters of the character set that
+    // wasn't selected
+    let (c1, c2) = match config.char_set {
+        Some(crate::Standard) => (b'-', b'_'),
+        Some(crate::UrlSafe) => (b'+', b'/'),
+        None => (0, 0),
+    };
+    let mut rejected = match config.char_set {
+        Some(_) => _mm256_or_si256(
+            _mm256_cmpeq_epi8(input, _mm256_set1_epi8(c1 as _)),
+            _mm256_cmpeq_epi8(input, _mm256_set1_epi8(c2 as _)),
+        ),
+        None => _mm256_setzero_si256(),
+    };
+    // Add whitespace characters that aren't allowed to the mask
+    let rejected_space: &[u8] = match config.whitespace {
+        crate::Whitespace::Forbidden => b" \t\n\x0b\x0c\r",
+        crate::Whitespace::LineBreaks => b" \t\x0b\x0c",
+        crate::Whitespace::Any => b"",
+    };
+    for &c in rejected_space {
+        rejected = _mm256_or_si256(rejected, _mm256_cmpeq_epi8(input, _mm256_set1_epi8(c as _)));
+    }
     // Transform mask to u32
-    let invalid_mask = _mm256_movemask_epi8(non_match);
+    let invalid_mask = _mm256_movemask_epi8(_mm256_or_si256(non_match, rejected));
 
     // Step 2. Numbers & letters: compute 6-bit value for the 3 different
     // ranges by simply adjusting the ASCII value.
@@ -140,46 +164,58 @@ unsafe fn decode_avx2(input: __m256i) -> (__m256i, u32, u32) {
     let second = _mm_extract_epi16(mask1, 0) as u16;
     let valid_mask = first as u32 + ((second as u32) << 16);
 
-    (result, invalid_mask as _, valid_mask as _)
+    // Step 5. Compute mask for padding characters.
+    let pad_mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(input, _mm256_set1_epi8(b'=' as _)));
+
+    (result, invalid_mask as _, valid_mask as _, pad_mask as _)
 }
 
 /// # Safety
 /// The caller should ensure the requisite CPU features are enabled.
 #[target_feature(enable = "avx2,bmi1,sse4.2,popcnt")]
-unsafe fn decode_block(block: &mut <Avx2 as super::Decoder>::Block) -> super::BlockResult {
+unsafe fn decode_block(
+    block: &mut <Avx2 as super::Decoder>::Block,
+    config: crate::DecodeConfig,
+) -> super::BlockResult {
     let input = array_as_m256i(*block);
 
-    let (unpacked, invalid_mask, mut valid_mask) = decode_avx2(input);
-
-    let unpacked = m256i_as_array(unpacked);
-
-    let first_invalid = match invalid_mask.trailing_zeros() {
-        32 => None,
-        v => Some(v as _),
-    };
-    let out_length = valid_mask.count_ones() as _;
-
-    let mut out_iter = block.iter_mut();
-    // TODO: Optimize loop (https://github.com/fortanix/b64-ct/issues/2)
-    for &val in unpacked.iter() {
-        if (valid_mask & 1) == 1 {
-            *out_iter.next().unwrap() = val;
+    let (unpacked, invalid, valid, pad) = decode_avx2(input, config);
+
+    // The decoded sextets are wiped when dropped
+    let unpacked = Scratch(m256i_as_array(unpacked));
+
+    let out_length = valid.count_ones() as _;
+
+    if config.oblivious {
+        *block = *unpacked;
+        super::compact_oblivious(block, valid);
+    } else {
+        let mut valid_mask = valid;
+        let mut out_iter = block.iter_mut();
+        // TODO: Optimize loop (https://github.com/fortanix/b64-ct/issues/2)
+        for &val in unpacked.iter() {
+            if (valid_mask & 1) == 1 {
+                *out_iter.next().unwrap() = val;
+            }
+            valid_mask >>= 1;
         }
-        valid_mask >>= 1;
     }
 
     super::BlockResult {
         out_length,
-        first_invalid,
+        valid,
+        invalid,
+        pad,
     }
 }
 
 /// # Safety
 /// The caller should ensure the requisite CPU features are enabled.
 #[target_feature(enable = "avx2,bmi1,sse4.2,popcnt")]
-unsafe fn pack_block(input: &<Avx2 as super::Packer>::Input, output: &mut [u8]) {
-    assert_eq!(output.len(), <Avx2 as super::Packer>::OUT_BUF_LEN);
-
+unsafe fn pack_block(
+    input: &<Avx2 as super::Packer>::Input,
+    output: &mut <Avx2 as super::Packer>::Output,
+) {
     let unpacked = array_as_m256i(*input);
 
     // Pack 32× 6-bit values into 16× 12-bit values
@@ -224,9 +260,13 @@ impl super::Decoder for Avx2 {
     type Block = [u8; 32];
 
     #[inline]
-    fn decode_block(self, block: &mut Self::Block) -> super::BlockResult {
+    fn decode_block(
+        self,
+        block: &mut Self::Block,
+        config: crate::DecodeConfig,
+    ) -> super::BlockResult {
         // safe: `self` was given as a witness that the features are available
-        unsafe { decode_block(block) }
+        unsafe { decode_block(block, config) }
     }
 
     #[inline(always)]
@@ -237,9 +277,9 @@ impl super::Decoder for Avx2 {
 
 impl super::Packer for Avx2 {
     type Input = [u8; 32];
-    const OUT_BUF_LEN: usize = 28;
+    type Output = [u8; 28];
 
-    fn pack_block(self, input: &Self::Input, output: &mut [u8]) {
+    fn pack_block(self, input: &Self::Input, output: &mut Self::Output) {
         // safe: `self` was given as a witness that the features are available
         unsafe { pack_block(input, output) }
     }
@@ -0,0 +1,139 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decoding at compile time, for base64 literals.
+//!
+//! The accepted input is identical to that of `FromBase64::from_base64`.
+//! Since this runs at compile time, it isn't resistant against side-channel
+//! attacks, and it doesn't need to be.
+
+use super::lut_align64::{INVALID_VALUE, LUT1_VALUES, LUT2_VALUES, PAD_VALUE, SPACE_VALUE};
+
+const fn lookup(b: u8) -> u8 {
+    match b {
+        0..=63 => LUT1_VALUES[b as usize],
+        64..=127 => LUT2_VALUES[(b - 64) as usize],
+        _ => INVALID_VALUE,
+    }
+}
+
+pub(crate) const fn decoded_len(input: &[u8]) -> usize {
+    let mut chars = 0;
+    let mut pads = 0;
+    let mut i = 0;
+    while i < input.len() {
+        match lookup(input[i]) {
+            INVALID_VALUE => panic!("invalid character in base64 input"),
+            SPACE_VALUE => {}
+            PAD_VALUE => pads += 1,
+            _ if pads > 0 => panic!("invalid character in base64 input after padding"),
+            _ => chars += 1,
+        }
+        i += 1;
+    }
+
+    if chars % 4 == 1 {
+        panic!("invalid base64 input length");
+    }
+    if pads > 2 || (pads > 0 && (chars + pads) % 4 != 0) {
+        panic!("invalid base64 padding");
+    }
+    chars * 3 / 4
+}
+
+pub(crate) const fn decode<const N: usize>(input: &[u8]) -> [u8; N] {
+    if decoded_len(input) != N {
+        panic!("base64 input doesn't decode to the expected length");
+    }
+
+    let mut output = [0u8; N];
+    let mut pos = 0;
+    let mut acc = 0u32;
+    let mut bits = 0;
+    let mut i = 0;
+    while i < input.len() {
+        let value = lookup(input[i]);
+        if value < 64 {
+            acc = (acc << 6) | value as u32;
+            bits += 6;
+            if bits >= 8 {
+                bits -= 8;
+                output[pos] = (acc >> bits) as u8;
+                acc &= (1 << bits) - 1;
+                pos += 1;
+            }
+        }
+        i += 1;
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{FromBase64, ToBase64, MIME, STANDARD, URL_SAFE};
+
+    #[test]
+    fn same_as_from_base64() {
+        for len in 0..100 {
+            let data: std::vec::Vec<u8> = (0..len).map(|_| rand::random()).collect();
+            for config in &[STANDARD, URL_SAFE, MIME] {
+                let input = data.to_base64(*config);
+                assert_eq!(decoded_len(input.as_bytes()), len);
+            }
+        }
+
+        let data: [u8; 32] = rand::random();
+        for config in &[STANDARD, URL_SAFE, MIME] {
+            let input = data.to_base64(*config);
+            assert_eq!(decode::<32>(input.as_bytes()), data);
+        }
+        assert_eq!(decode::<4>(b" Zm9v\r\nYg = = "), *b"foob");
+        assert_eq!(
+            &decode::<4>(b"Zm9vYh")[..],
+            &"Zm9vYh".from_base64().unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn literal() {
+        const KEY: [u8; 6] = crate::b64!("Zm9v YmFy");
+        assert_eq!(&KEY, b"foobar");
+        assert_eq!(crate::b64!("Zg=="), *b"f");
+        assert_eq!(crate::b64!(""), [0u8; 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid character in base64 input")]
+    fn invalid_character() {
+        decoded_len(b"Zm$v");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid character in base64 input after padding")]
+    fn data_after_padding() {
+        decoded_len(b"Zg==Zg==");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid base64 input length")]
+    fn invalid_length() {
+        decoded_len(b"Zm9vY");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid base64 padding")]
+    fn invalid_padding() {
+        decoded_len(b"Zm9v=");
+    }
+
+    #[test]
+    #[should_panic(expected = "base64 input doesn't decode to the expected length")]
+    fn wrong_length() {
+        decode::<4>(b"Zm9v");
+    }
+}
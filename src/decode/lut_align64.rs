@@ -4,12 +4,20 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-const INVALID_VALUE: u8 = 0x80;
-const SPACE_VALUE: u8 = 0x40;
+pub(super) const INVALID_VALUE: u8 = 0x80;
+pub(super) const SPACE_VALUE: u8 = 0x40;
+// Padding is skipped like whitespace, but also reported separately.
+pub(super) const PAD_VALUE: u8 = 0x41;
 
 use crate::lut_align64::CacheLineLut;
 
-static LUT1: CacheLineLut = CacheLineLut([
+// The tables are also used at compile time by `decode_const`, which can't
+// read statics.
+static LUT1: CacheLineLut = CacheLineLut(LUT1_VALUES);
+static LUT2: CacheLineLut = CacheLineLut(LUT2_VALUES);
+
+/// Lookup table for inputs 0 to 63.
+pub(super) const LUT1_VALUES: [u8; 64] = [
     INVALID_VALUE, // input 0 (0x0)
     INVALID_VALUE, // input 1 (0x1)
     INVALID_VALUE, // input 2 (0x2)
@@ -21,7 +29,7 @@ static LUT1: CacheLineLut = CacheLineLut([
     INVALID_VALUE, // input 8 (0x8)
     SPACE_VALUE,   // input 9 (0x9)
     SPACE_VALUE,   // input 10 (0xA)
-    INVALID_VALUE, // input 11 (0xB)
+    SPACE_VALUE,   // input 11 (0xB)
     SPACE_VALUE,   // input 12 (0xC)
     SPACE_VALUE,   // input 13 (0xD)
     INVALID_VALUE, // input 14 (0xE)
@@ -71,12 +79,13 @@ static LUT1: CacheLineLut = CacheLineLut([
     INVALID_VALUE, // input 58 (0x3A)
     INVALID_VALUE, // input 59 (0x3B)
     INVALID_VALUE, // input 60 (0x3C)
-    INVALID_VALUE, // input 61 (0x3D)
+    PAD_VALUE,     // input 61 (0x3D char '=')
     INVALID_VALUE, // input 62 (0x3E)
     INVALID_VALUE, // input 63 (0x3F)
-]);
+];
 
-static LUT2: CacheLineLut = CacheLineLut([
+/// Lookup table for inputs 64 to 127.
+pub(super) const LUT2_VALUES: [u8; 64] = [
     INVALID_VALUE, // input 64 (0x40)
     0,             // input 65 (0x41 char 'A') => 0 (0x0)
     1,             // input 66 (0x42 char 'B') => 1 (0x1)
@@ -141,9 +150,9 @@ static LUT2: CacheLineLut = CacheLineLut([
     INVALID_VALUE, // input 125 (0x7D)
     INVALID_VALUE, // input 126 (0x7E)
     INVALID_VALUE, // input 127 (0x7F)
-]);
+];
 
-fn decode64(b: u8) -> (u8, bool, bool) {
+fn decode64(b: u8) -> (u8, bool, bool, bool) {
     let idx = (b % 64) as usize;
 
     /* This is basically what
@@ -156,13 +165,14 @@ fn decode64(b: u8) -> (u8, bool, bool) {
      * ```
      * compiles into.
      */
-    let mask = (-(((b & 0xc0) == 0x40) as bool as i8)) as u8;
+    let mask = (-(((b & 0xc0) == 0x40) as i8)) as u8;
 
     let looked_up = ((!mask) & LUT1.0[idx]) | (mask & LUT2.0[idx]);
     (
         looked_up,
         ((b | looked_up) as i8).is_negative(),
         (looked_up & SPACE_VALUE) == SPACE_VALUE,
+        looked_up == PAD_VALUE,
     )
 }
 
@@ -173,14 +183,22 @@ impl super::Decoder for LutAlign64 {
     type Block = [u8; 1];
 
     #[inline]
-    fn decode_block(self, block: &mut Self::Block) -> super::BlockResult {
-        let (a, invalid, space) = decode64(block[0]);
+    fn decode_block(
+        self,
+        block: &mut Self::Block,
+        config: crate::DecodeConfig,
+    ) -> super::BlockResult {
+        let (a, invalid, space, pad) = decode64(block[0]);
+        let invalid = invalid | (super::is_rejected(block[0], config) != 0);
+        let valid = !(space | invalid);
 
         block[0] = a;
 
         super::BlockResult {
-            out_length: if space | invalid { 0 } else { 1 },
-            first_invalid: if invalid { Some(0) } else { None },
+            out_length: valid as u8,
+            valid: valid as u32,
+            invalid: invalid as u32,
+            pad: pad as u32,
         }
     }
 
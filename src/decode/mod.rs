@@ -4,22 +4,41 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+mod arithmetic;
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 mod avx2;
+pub(crate) mod literal;
 mod lut_align64;
+#[cfg(feature = "std")]
+mod read;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod ssse3;
+mod swar;
+
+#[cfg(feature = "std")]
+pub use self::read::DecoderReader;
 
 use alloc::vec::Vec;
 use core::cmp;
 use core::fmt;
 
+use crate::misc::{wipe, wipe_output, wipe_vec, Scratch};
+
+/// Classification of the characters of a block. Bit `i` of each mask
+/// corresponds to character `i` of the block.
 #[must_use]
 struct BlockResult {
     out_length: u8,
-    first_invalid: Option<u8>,
+    /// Characters that were decoded.
+    valid: u32,
+    /// Characters that aren't accepted.
+    invalid: u32,
+    /// `=` characters. These are neither valid nor invalid.
+    pad: u32,
 }
 
 /// Errors that can occur when decoding a base64 encoded string
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     /// The input had an invalid length.
     InvalidLength,
@@ -28,6 +47,24 @@ pub enum Error {
     /// The input contained a character (at the given index) not part of the
     /// base64 format.
     InvalidCharacter(usize),
+    /// The output buffer is too small to hold the decoded output.
+    BufferTooSmall,
+    /// The character at the given index encodes non-zero bits that are
+    /// discarded when decoding. Only returned if `DecodeConfig::canonical` is
+    /// set.
+    NonCanonical(usize),
+    /// The input wasn't padded. Only returned if `DecodeConfig::padding` is
+    /// `Padding::Required`.
+    MissingPadding,
+    /// The input contained a padding character at the given index. Only
+    /// returned if `DecodeConfig::padding` is `Padding::Forbidden`.
+    UnexpectedPadding(usize),
+    /// Memory for the output couldn't be allocated. Only returned by the
+    /// `try_` functions.
+    AllocationFailed,
+    /// The decoded data didn't have the expected length. Only returned by
+    /// `decode_array`.
+    WrongLength,
 }
 
 impl fmt::Display for Error {
@@ -36,19 +73,23 @@ impl fmt::Display for Error {
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 trait Decoder: Copy {
     type Block: AsRef<[u8]> + AsMut<[u8]>;
 
-    fn decode_block(self, block: &mut Self::Block) -> BlockResult;
+    fn decode_block(self, block: &mut Self::Block, config: crate::DecodeConfig) -> BlockResult;
     fn zero_block() -> Self::Block;
 }
 
 trait Packer: Copy {
     type Input: AsRef<[u8]> + AsMut<[u8]> + Default;
-    const OUT_BUF_LEN: usize;
+    /// Only the first `size_of::<Self::Input>() / 4 * 3` bytes of the output
+    /// are meaningful, the remaining bytes are scratch space.
+    type Output: AsRef<[u8]> + AsMut<[u8]> + Default;
 
-    /// The caller should pass `output` as a slice with length `OUT_BUF_LEN`.
-    fn pack_block(self, input: &Self::Input, output: &mut [u8]);
+    fn pack_block(self, input: &Self::Input, output: &mut Self::Output);
 }
 
 #[derive(Copy, Clone)]
@@ -56,24 +97,87 @@ struct Simple;
 
 impl Packer for Simple {
     type Input = [u8; 4];
-    const OUT_BUF_LEN: usize = 3;
+    type Output = [u8; 3];
 
     #[inline]
-    fn pack_block(self, input: &Self::Input, output: &mut [u8]) {
+    #[allow(clippy::identity_op)]
+    fn pack_block(self, input: &Self::Input, output: &mut Self::Output) {
         output[0] = (input[0] << 2) | (input[1] >> 4);
         output[1] = (input[1] << 4) | (input[2] >> 2);
         output[2] = (input[2] << 6) | (input[3] >> 0);
     }
 }
 
+/// A destination for decoded output.
+trait Output {
+    fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error>;
+
+    /// Append the first `len` bytes of `data`. Where possible, all of `data`
+    /// is written and the rest is dropped afterwards, so that the length of
+    /// the write doesn't depend on `len`.
+    fn extend_oblivious(&mut self, data: &[u8], len: usize) -> Result<(), Error> {
+        self.extend_from_slice(&data[..len])
+    }
+}
+
+impl Output for Vec<u8> {
+    fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.try_reserve(data.len())
+            .map_err(|_| Error::AllocationFailed)?;
+        Vec::extend_from_slice(self, data);
+        Ok(())
+    }
+
+    fn extend_oblivious(&mut self, data: &[u8], len: usize) -> Result<(), Error> {
+        let start = self.len();
+        Output::extend_from_slice(self, data)?;
+        self.truncate(start + len);
+        Ok(())
+    }
+}
+
+struct ExtendOutput<'a, T: ?Sized>(&'a mut T);
+
+impl<T: Extend<u8> + ?Sized> Output for ExtendOutput<'_, T> {
+    fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.0.extend(data.iter().cloned());
+        Ok(())
+    }
+}
+
+/// Like `std::io::Write for &mut [u8]`, writing advances the slice past the
+/// written data.
+impl Output for &mut [u8] {
+    fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        if self.len() < data.len() {
+            return Err(Error::BufferTooSmall);
+        }
+        let (head, tail) = core::mem::take(self).split_at_mut(data.len());
+        head.copy_from_slice(data);
+        *self = tail;
+        Ok(())
+    }
+
+    /// The write is clipped at the end of the slice.
+    fn extend_oblivious(&mut self, data: &[u8], len: usize) -> Result<(), Error> {
+        if self.len() < len {
+            return Err(Error::BufferTooSmall);
+        }
+        let written = cmp::min(data.len(), self.len());
+        self[..written].copy_from_slice(&data[..written]);
+        *self = &mut core::mem::take(self)[len..];
+        Ok(())
+    }
+}
+
 struct PackState<P: Packer> {
     packer: P,
-    cache: P::Input,
+    cache: Scratch<P::Input>,
     pos: usize,
 }
 
 impl<P: Packer> PackState<P> {
-    fn extend(&mut self, mut input: &[u8], out: &mut Vec<u8>) {
+    fn extend<O: Output>(&mut self, mut input: &[u8], out: &mut O) -> Result<(), Error> {
         while !input.is_empty() {
             let (_, cache_end) = self.cache.as_mut().split_at_mut(self.pos);
             let (input_start, input_rest) = input.split_at(cmp::min(input.len(), cache_end.len()));
@@ -82,107 +186,681 @@ impl<P: Packer> PackState<P> {
             if input_start.len() != cache_end.len() {
                 self.pos += input_start.len();
             } else {
-                let out_start = out.len();
-                out.resize(out_start + P::OUT_BUF_LEN, 0);
-                self.packer.pack_block(&self.cache, &mut out[out_start..]);
-                out.truncate(out_start + (core::mem::size_of::<P::Input>() / 4 * 3));
+                let mut packed = Scratch(P::Output::default());
+                self.packer.pack_block(&self.cache, &mut packed);
+                out.extend_from_slice(
+                    &packed.as_ref()[..(core::mem::size_of::<P::Input>() / 4 * 3)],
+                )?;
                 self.pos = 0;
             }
         }
+        Ok(())
     }
 
-    fn flush(&mut self, out: &mut Vec<u8>, trailer_length: Option<usize>) -> Result<(), Error> {
+    /// Check that the cached input and `pad_count` padding characters form a
+    /// valid trailer.
+    fn check_trailer(&self, pad_count: usize, padding: crate::Padding) -> Result<(), Error> {
         if self.pos % 4 == 1 {
             return Err(Error::InvalidLength);
         }
 
-        if let Some(trailer_length) = trailer_length {
-            if (self.pos + trailer_length) % 4 != 0 {
-                return Err(Error::InvalidTrailer);
+        match (pad_count, padding) {
+            (0, crate::Padding::Required) => {
+                if self.pos % 4 != 0 {
+                    return Err(Error::MissingPadding);
+                }
+            }
+            (0, _) => {}
+            (pad_count, _) => {
+                if (self.pos + pad_count) % 4 != 0 {
+                    return Err(Error::InvalidTrailer);
+                }
             }
         }
+        Ok(())
+    }
 
+    /// Decode the cached input, which must have passed `check_trailer`.
+    fn flush<O: Output>(&mut self, out: &mut O) -> Result<(), Error> {
         self.cache.as_mut()[self.pos] = 0;
-        let out_start = out.len();
-        out.resize(out.len() + P::OUT_BUF_LEN, 0);
-        self.packer.pack_block(&self.cache, &mut out[out_start..]);
-        out.truncate(out_start + (self.pos * 3 / 4));
+        let mut packed = Scratch(P::Output::default());
+        self.packer.pack_block(&self.cache, &mut packed);
+        out.extend_from_slice(&packed.as_ref()[..(self.pos * 3 / 4)])
+    }
+
+    /// Like `extend`, but with control flow and memory access that only
+    /// depend on `block.len()`, not on `len`. The first `len` bytes of
+    /// `block` are the input.
+    ///
+    /// The output is written with `Output::extend_oblivious`, so only the
+    /// position in the output depends on `len`.
+    fn extend_oblivious<O: Output>(
+        &mut self,
+        block: &[u8],
+        len: usize,
+        out: &mut O,
+    ) -> Result<(), Error> {
+        const BUF_LEN: usize = 64;
+        let input_len = core::mem::size_of::<P::Input>();
+        assert!(input_len + block.len() <= BUF_LEN);
+
+        // Shift the block right by `self.pos`, one bit of `self.pos` at a
+        // time, and merge it with the cached input.
+        let mut shifted = Scratch([0u8; BUF_LEN]);
+        shifted[..block.len()].copy_from_slice(block);
+        let mut step = 1;
+        while step < BUF_LEN {
+            let mask = all_ones_if(self.pos & step != 0) as u8;
+            for i in (step..BUF_LEN).rev() {
+                shifted[i] = select(mask, shifted[i - step], shifted[i]);
+            }
+            step <<= 1;
+        }
+        let mut buf = Scratch([0u8; BUF_LEN]);
+        buf[..input_len].copy_from_slice(self.cache.as_ref());
+        for (i, (b, &s)) in buf.iter_mut().zip(shifted.iter()).enumerate() {
+            *b = select(all_ones_if(i < self.pos) as u8, *b, s);
+        }
+        let mut pos = self.pos + len;
+
+        // Pack as many times as there could be complete inputs, only
+        // outputting and consuming the complete ones.
+        for _ in 0..block.len().div_ceil(input_len) {
+            let full = pos >= input_len;
+            let mut input = Scratch(P::Input::default());
+            input.as_mut().copy_from_slice(&buf[..input_len]);
+            let mut packed = Scratch(P::Output::default());
+            self.packer.pack_block(&input, &mut packed);
+            // Incomplete input is zeroed, so that it isn't left behind in the
+            // part of the write that is dropped.
+            let mask = all_ones_if(full) as u8;
+            for b in packed.as_mut() {
+                *b &= mask;
+            }
+            let out_len = input_len / 4 * 3;
+            out.extend_oblivious(&packed.as_ref()[..out_len], full as usize * out_len)?;
+
+            for i in 0..(BUF_LEN - input_len) {
+                buf[i] = select(mask, buf[i + input_len], buf[i]);
+            }
+            pos -= full as usize * input_len;
+        }
+
+        self.cache.as_mut().copy_from_slice(&buf[..input_len]);
+        self.pos = pos;
         Ok(())
     }
+
+    /// The bits of the cached input that will be discarded by `flush`.
+    fn discarded_bits(&self) -> u8 {
+        // The mask only depends on the length of the input, so this doesn't
+        // branch on or index by secret data.
+        const MASKS: [u8; 4] = [0, 0, 0x0f, 0x03];
+        match self.pos.checked_sub(1) {
+            Some(last) => self.cache.as_ref()[last] & MASKS[self.pos % 4],
+            None => 0,
+        }
+    }
+}
+
+/// Incremental decoding state.
+///
+/// Input may be split at arbitrary positions. A partial block at the end of
+/// an input chunk is padded with whitespace, so only the packer cache and the
+/// trailer need to be carried over to the next chunk.
+///
+/// The trailer is validated for every block using the masks in
+/// `BlockResult`, so the control flow and memory access pattern don't depend
+/// on where the `=` characters are. Only when an error is found is the
+/// position of the error computed.
+struct DecodeState<D: Decoder, P: Packer> {
+    config: crate::DecodeConfig,
+    decoder: D,
+    packer: PackState<P>,
+    /// Position of the start of the next input chunk, for error reporting.
+    offset: usize,
+    /// The number of `=` characters seen so far.
+    pad_count: usize,
+    /// All ones if the trailer has started, zero otherwise.
+    after_pad: u32,
+    /// Position of the last character that was decoded, for error reporting.
+    last_valid_pos: usize,
 }
 
-fn decode64<D: Decoder, P: Packer>(input: &[u8], decoder: D, packer: P) -> Result<Vec<u8>, Error> {
-    if input.is_empty() {
-        return Ok(Vec::new());
+impl<D: Decoder, P: Packer> DecodeState<D, P> {
+    fn new(config: crate::DecodeConfig, decoder: D, packer: P) -> Self {
+        DecodeState {
+            config,
+            decoder,
+            packer: PackState {
+                packer,
+                cache: Scratch(P::Input::default()),
+                pos: 0,
+            },
+            offset: 0,
+            pad_count: 0,
+            after_pad: 0,
+            last_valid_pos: 0,
+        }
     }
 
-    let p_in_len = core::mem::size_of::<P::Input>();
-    let p_out_len = p_in_len / 4 * 3;
-    let cap =
-        crate::misc::div_roundup(input.len(), p_in_len) * p_out_len - p_out_len + P::OUT_BUF_LEN;
-    let mut out = Vec::with_capacity(cap);
-
-    let mut packer = PackState::<P> {
-        packer,
-        cache: P::Input::default(),
-        pos: 0,
-    };
+    /// The maximum number of bytes `update` may output for `input_len` bytes
+    /// of input.
+    fn max_update_len(&self, input_len: usize) -> usize {
+        // Output is produced in multiples of `size_of::<P::Input>()`, which
+        // is a multiple of 4.
+        (self.packer.pos + input_len) / 4 * 3
+    }
 
-    let mut trailer_length = None;
-    for (chunk, chunk_start) in input
-        .chunks(core::mem::size_of::<D::Block>())
-        .zip((0..).step_by(core::mem::size_of::<D::Block>()))
-    {
-        let mut block = D::zero_block();
-        block.as_mut()[..chunk.len()].copy_from_slice(chunk);
-        let result = decoder.decode_block(&mut block);
-
-        if let Some(idx) = result.first_invalid {
-            let idx = idx as usize;
-            if input[chunk_start + idx] == b'=' {
-                let rest_start = chunk_start + idx + 1;
-                let rest = &input[rest_start..];
-                let mut iter = rest
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, c)| !c.is_ascii_whitespace());
-                trailer_length = match (iter.next(), iter.next()) {
-                    (None, _) => Some(1),
-                    (Some((_, b'=')), None) => Some(2),
-                    (Some((_, b'=')), Some((i, _))) | (Some((i, _)), _) => {
-                        return Err(Error::InvalidCharacter(rest_start + i))
-                    }
-                };
+    /// The maximum number of bytes `finish` may output.
+    fn max_finish_len(&self) -> usize {
+        self.packer.pos * 3 / 4
+    }
+
+    fn update<O: Output>(&mut self, input: &[u8], out: &mut O) -> Result<(), Error> {
+        let forbid_pad = all_ones_if(matches!(self.config.padding, crate::Padding::Forbidden));
+
+        for (chunk, chunk_start) in input
+            .chunks(core::mem::size_of::<D::Block>())
+            .zip((0..).step_by(core::mem::size_of::<D::Block>()))
+        {
+            let mut block = Scratch(D::zero_block());
+            block.as_mut()[..chunk.len()].copy_from_slice(chunk);
+            let result = self.decoder.decode_block(&mut block, self.config);
+
+            // Everything following the first `=` is part of the trailer.
+            let first_pad = result.pad & result.pad.wrapping_neg();
+            let after_pad = self.after_pad | !(first_pad | first_pad.wrapping_sub(1));
+
+            // Up to two `=` characters are allowed in total. Clear the ones
+            // that are still allowed, whatever is left is in excess.
+            let mut excess_pad = result.pad;
+            let mut pads_allowed = 2 - self.pad_count;
+            for _ in 0..2 {
+                let allowed = all_ones_if(pads_allowed > 0);
+                excess_pad &= !(excess_pad & excess_pad.wrapping_neg() & allowed);
+                pads_allowed -= (pads_allowed > 0) as usize;
+            }
+
+            // The whitespace padding a short chunk may be rejected by the
+            // whitespace policy, so ignore anything past the chunk.
+            let chunk_mask = u32::MAX >> (32 - chunk.len());
+            let unexpected_pad = result.pad & forbid_pad;
+            let errors =
+                (result.invalid | (result.valid & after_pad) | excess_pad | unexpected_pad)
+                    & chunk_mask;
+            if errors != 0 {
+                let idx = errors.trailing_zeros();
+                let pos = self.offset + chunk_start + idx as usize;
+                return Err(if unexpected_pad & (1 << idx) != 0 {
+                    Error::UnexpectedPadding(pos)
+                } else {
+                    Error::InvalidCharacter(pos)
+                });
+            }
+
+            let last_valid = 31u32.wrapping_sub(result.valid.leading_zeros()) as usize;
+            let any_valid = 0usize.wrapping_sub((result.valid != 0) as usize);
+            self.last_valid_pos = ((self.offset + chunk_start + last_valid) & any_valid)
+                | (self.last_valid_pos & !any_valid);
+
+            self.pad_count += result.pad.count_ones() as usize;
+            self.after_pad |= all_ones_if(result.pad != 0);
+
+            if self.config.oblivious {
+                self.packer
+                    .extend_oblivious(block.as_ref(), result.out_length as _, out)?;
             } else {
-                return Err(Error::InvalidCharacter(chunk_start + idx));
+                self.packer
+                    .extend(&block.as_ref()[..(result.out_length as _)], out)?;
             }
         }
 
-        packer.extend(&block.as_ref()[..(result.out_length as _)], &mut out);
+        self.offset += input.len();
+        Ok(())
+    }
+
+    /// Decode any cached input and check the trailer. The state is reset for
+    /// decoding a new input.
+    fn finish<O: Output>(&mut self, out: &mut O) -> Result<(), Error> {
+        let result = self
+            .packer
+            .check_trailer(self.pad_count, self.config.padding)
+            .and_then(|()| {
+                if self.config.canonical && self.packer.discarded_bits() != 0 {
+                    Err(Error::NonCanonical(self.last_valid_pos))
+                } else {
+                    self.packer.flush(out)
+                }
+            });
+        wipe(self.packer.cache.as_mut());
+        self.packer.pos = 0;
+        self.offset = 0;
+        self.pad_count = 0;
+        self.after_pad = 0;
+        result
+    }
+}
+
+/// Returns `0xff` if `b` is a special character of a character set other than
+/// the selected one, or whitespace that is not allowed, `0` otherwise. Only
+/// the configuration is branched on, not `b`.
+fn is_rejected(b: u8, config: crate::DecodeConfig) -> u8 {
+    use crate::arithmetic::eq;
+
+    let special = match config.char_set {
+        Some(crate::Standard) => eq(b, b'-') | eq(b, b'_'),
+        Some(crate::UrlSafe) => eq(b, b'+') | eq(b, b'/'),
+        None => 0,
+    };
+
+    let line_break = eq(b, b'\n') | eq(b, b'\r');
+    let other_space = eq(b, b' ') | eq(b, b'\t') | eq(b, 0x0b) | eq(b, 0x0c);
+    let space = match config.whitespace {
+        crate::Whitespace::Forbidden => line_break | other_space,
+        crate::Whitespace::LineBreaks => other_space,
+        crate::Whitespace::Any => 0,
+    };
+
+    special | space
+}
+
+/// Returns a mask of all ones if `cond` is true, zero otherwise.
+fn all_ones_if(cond: bool) -> u32 {
+    0u32.wrapping_sub(cond as u32)
+}
+
+/// Returns `a` if `mask` is all ones, `b` if `mask` is zero.
+fn select(mask: u8, a: u8, b: u8) -> u8 {
+    (a & mask) | (b & !mask)
+}
+
+/// Moves the bytes of `block` selected by `valid` to the start of `block`,
+/// with control flow and memory access that only depend on `block.len()`.
+///
+/// Every selected byte moves down by the number of unselected bytes before
+/// it. The moves are done one bit of that distance at a time, starting with
+/// the lowest bit, which guarantees that selected bytes never collide.
+fn compact_oblivious(block: &mut [u8], valid: u32) {
+    assert!(block.len() <= 32);
+    #[cfg(test)]
+    tests::trace(tests::Event::Compact(block.len()));
+
+    let mut distance = [0u8; 32];
+    let mut selected = [0u8; 32];
+    let mut skipped = 0;
+    for i in 0..block.len() {
+        let v = ((valid >> i) & 1) as u8;
+        distance[i] = skipped;
+        selected[i] = 0u8.wrapping_sub(v);
+        skipped += 1 - v;
+    }
+
+    let mut step = 1;
+    while step < block.len() {
+        for i in step..block.len() {
+            let mask = selected[i] & 0u8.wrapping_sub((distance[i] & step as u8 != 0) as u8);
+            block[i - step] = select(mask, block[i], block[i - step]);
+            distance[i - step] = select(mask, distance[i], distance[i - step]);
+            selected[i - step] |= mask;
+            selected[i] &= !mask;
+        }
+        step <<= 1;
+    }
+}
+
+fn decode64_to<D: Decoder, P: Packer, O: Output>(
+    input: &[u8],
+    config: crate::DecodeConfig,
+    decoder: D,
+    packer: P,
+    out: &mut O,
+) -> Result<(), Error> {
+    let mut state = DecodeState::new(config, decoder, packer);
+    state.update(input, out)?;
+    state.finish(out)
+}
+
+/// The capacity needed to decode `input_len` bytes of input without
+/// reallocating. This includes room for the last write of
+/// `PackState::extend_oblivious`, which may extend past the output.
+fn decode64_capacity<P: Packer>(input_len: usize) -> usize {
+    let p_in_len = core::mem::size_of::<P::Input>();
+    (input_len / p_in_len + 1) * (p_in_len / 4 * 3)
+}
+
+fn decode64<D: Decoder, P: Packer>(
+    input: &[u8],
+    config: crate::DecodeConfig,
+    decoder: D,
+    packer: P,
+) -> Result<Vec<u8>, Error> {
+    let out = Vec::with_capacity(decode64_capacity::<P>(input.len()));
+    decode64_into(input, config, decoder, packer, out)
+}
+
+/// Like `decode64`, but returns `Error::AllocationFailed` instead of aborting
+/// if the output can't be allocated.
+fn try_decode64<D: Decoder, P: Packer>(
+    input: &[u8],
+    config: crate::DecodeConfig,
+    decoder: D,
+    packer: P,
+) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    out.try_reserve_exact(decode64_capacity::<P>(input.len()))
+        .map_err(|_| Error::AllocationFailed)?;
+    decode64_into(input, config, decoder, packer, out)
+}
+
+/// Decode into `out`, which must have at least `decode64_capacity` spare
+/// capacity. The output is then never moved to a new allocation, which would
+/// leave a copy behind and could fail.
+fn decode64_into<D: Decoder, P: Packer>(
+    input: &[u8],
+    config: crate::DecodeConfig,
+    decoder: D,
+    packer: P,
+    mut out: Vec<u8>,
+) -> Result<Vec<u8>, Error> {
+    match decode64_to(input, config, decoder, packer, &mut out) {
+        Ok(()) => Ok(out),
+        Err(e) => {
+            wipe_vec(&mut out);
+            Err(e)
+        }
+    }
+}
+
+fn decode64_to_slice<D: Decoder, P: Packer>(
+    input: &[u8],
+    config: crate::DecodeConfig,
+    decoder: D,
+    packer: P,
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    write_to_slice(output, |out| {
+        decode64_to(input, config, decoder, packer, out)
+    })
+}
+
+/// Writes to the beginning of `output` using `f`, and returns the number of
+/// bytes written. If `f` fails, the bytes written are wiped.
+fn write_to_slice<F>(output: &mut [u8], f: F) -> Result<usize, Error>
+where
+    F: FnOnce(&mut &mut [u8]) -> Result<(), Error>,
+{
+    let output_len = output.len();
+    let mut out = &mut *output;
+    let result = f(&mut out);
+    let written = output_len - out.len();
+    match result {
+        Ok(()) => Ok(written),
+        Err(e) => {
+            wipe_output(&mut output[..written]);
+            Err(e)
+        }
+    }
+}
 
-        if trailer_length.is_some() {
-            break;
+fn decode64_to_array<D: Decoder, P: Packer, const N: usize>(
+    input: &[u8],
+    config: crate::DecodeConfig,
+    decoder: D,
+    packer: P,
+) -> Result<[u8; N], Error> {
+    let mut output = [0u8; N];
+    let mut out = ArrayOutput {
+        buf: &mut output,
+        len: 0,
+    };
+    // Only the length of the decoded data determines which branch is taken.
+    let result = match decode64_to(input, config, decoder, packer, &mut out) {
+        Ok(()) if out.len != N => Err(Error::WrongLength),
+        result => result,
+    };
+    match result {
+        Ok(()) => Ok(output),
+        Err(e) => {
+            wipe_output(&mut output);
+            Err(e)
         }
     }
+}
+
+/// An output of fixed size that counts, but drops, any output that doesn't
+/// fit. This way, the whole input is still validated if it decodes to too
+/// many bytes.
+struct ArrayOutput<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
 
-    packer.flush(&mut out, trailer_length)?;
+impl Output for ArrayOutput<'_> {
+    fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.extend_oblivious(data, data.len())
+    }
 
-    Ok(out)
+    fn extend_oblivious(&mut self, data: &[u8], len: usize) -> Result<(), Error> {
+        let start = cmp::min(self.len, self.buf.len());
+        let written = cmp::min(data.len(), self.buf.len() - start);
+        self.buf[start..start + written].copy_from_slice(&data[..written]);
+        self.len += len;
+        Ok(())
+    }
 }
 
-pub(super) fn decode64_arch(input: &[u8]) -> Result<Vec<u8>, Error> {
+/// An incremental decoder using the implementation selected by
+/// [`crate::backend()`].
+enum ArchDecoder {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    unsafe {
-        if is_x86_feature_detected!("avx2")
-            && is_x86_feature_detected!("bmi1")
-            && is_x86_feature_detected!("sse4.2")
-            && is_x86_feature_detected!("popcnt")
-        {
-            let avx2 = avx2::Avx2::new();
-            return decode64(input, avx2, avx2);
+    Avx2(DecodeState<avx2::Avx2, avx2::Avx2>),
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Ssse3(DecodeState<ssse3::Ssse3, ssse3::Ssse3>),
+    Swar(DecodeState<swar::Swar, swar::Swar>),
+    Arithmetic(DecodeState<arithmetic::Arithmetic, Simple>),
+    LutAlign64(DecodeState<lut_align64::LutAlign64, Simple>),
+}
+
+/// Call a method on the `DecodeState` inside an `ArchDecoder`.
+macro_rules! arch_decoder_dispatch {
+    ($self:expr, $state:ident => $e:expr) => {
+        match $self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ArchDecoder::Avx2($state) => $e,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ArchDecoder::Ssse3($state) => $e,
+            ArchDecoder::Swar($state) => $e,
+            ArchDecoder::Arithmetic($state) => $e,
+            ArchDecoder::LutAlign64($state) => $e,
         }
+    };
+}
+
+impl ArchDecoder {
+    fn new(config: crate::DecodeConfig) -> ArchDecoder {
+        use crate::Backend;
+
+        // SAFETY: `backend()` only returns backends that are available on
+        // the runtime CPU.
+        match crate::backend() {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => unsafe {
+                let avx2 = avx2::Avx2::new();
+                ArchDecoder::Avx2(DecodeState::new(config, avx2, avx2))
+            },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Ssse3 => unsafe {
+                let ssse3 = ssse3::Ssse3::new();
+                ArchDecoder::Ssse3(DecodeState::new(config, ssse3, ssse3))
+            },
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            Backend::Avx2 | Backend::Ssse3 => unreachable!(),
+            Backend::Swar => ArchDecoder::Swar(DecodeState::new(config, swar::Swar, swar::Swar)),
+            Backend::Arithmetic => {
+                ArchDecoder::Arithmetic(DecodeState::new(config, arithmetic::Arithmetic, Simple))
+            }
+            Backend::LutAlign64 => {
+                ArchDecoder::LutAlign64(DecodeState::new(config, lut_align64::LutAlign64, Simple))
+            }
+        }
+    }
+
+    fn max_update_len(&self, input_len: usize) -> usize {
+        arch_decoder_dispatch!(self, state => state.max_update_len(input_len))
+    }
+
+    fn max_finish_len(&self) -> usize {
+        arch_decoder_dispatch!(self, state => state.max_finish_len())
+    }
+
+    fn update<O: Output>(&mut self, input: &[u8], out: &mut O) -> Result<(), Error> {
+        arch_decoder_dispatch!(self, state => state.update(input, out))
+    }
+
+    fn finish<O: Output>(&mut self, out: &mut O) -> Result<(), Error> {
+        arch_decoder_dispatch!(self, state => state.finish(out))
+    }
+}
+
+pub(super) fn decode64_arch(input: &[u8], config: crate::DecodeConfig) -> Result<Vec<u8>, Error> {
+    arch_decoder_dispatch!(ArchDecoder::new(config), state => {
+        decode64(input, config, state.decoder, state.packer.packer)
+    })
+}
+
+pub(super) fn try_decode64_arch(
+    input: &[u8],
+    config: crate::DecodeConfig,
+) -> Result<Vec<u8>, Error> {
+    arch_decoder_dispatch!(ArchDecoder::new(config), state => {
+        try_decode64(input, config, state.decoder, state.packer.packer)
+    })
+}
+
+pub(super) fn decode64_to_slice_arch(
+    input: &[u8],
+    config: crate::DecodeConfig,
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    arch_decoder_dispatch!(ArchDecoder::new(config), state => {
+        decode64_to_slice(input, config, state.decoder, state.packer.packer, output)
+    })
+}
+
+pub(super) fn decode64_to_array_arch<const N: usize>(
+    input: &[u8],
+    config: crate::DecodeConfig,
+) -> Result<[u8; N], Error> {
+    arch_decoder_dispatch!(ArchDecoder::new(config), state => {
+        decode64_to_array(input, config, state.decoder, state.packer.packer)
+    })
+}
+
+/// An incremental base64 decoder.
+///
+/// This decodes input that is provided in chunks, without allocating. The
+/// accepted input and output are identical to that of
+/// [`FromBase64::from_base64`] on the concatenation of all chunks. The index
+/// in `FromBase64Error::InvalidCharacter` is relative to the start of the
+/// first chunk.
+///
+/// If an error is returned by `update`, the decoder should not be used any
+/// further.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::Base64Decoder;
+///
+/// let mut decoder = Base64Decoder::new();
+/// let mut output = Vec::new();
+/// decoder.update(b"SGVsbG8s", &mut output).unwrap();
+/// decoder.update(b"IFdvcmxk", &mut output).unwrap();
+/// decoder.finalize(&mut output).unwrap();
+/// assert_eq!(output, b"Hello, World");
+/// ```
+///
+/// [`FromBase64::from_base64`]: crate::FromBase64::from_base64
+pub struct Base64Decoder(ArchDecoder);
+
+impl Base64Decoder {
+    /// Create a new decoder.
+    pub fn new() -> Base64Decoder {
+        Base64Decoder::with_config(crate::DecodeConfig::default())
+    }
+
+    /// Create a new decoder following the specified decoding configuration.
+    pub fn with_config(config: crate::DecodeConfig) -> Base64Decoder {
+        Base64Decoder(ArchDecoder::new(config))
+    }
+
+    /// Decode the next chunk of input, appending the output to `output`.
+    ///
+    /// Input that doesn't fill a complete block is cached until the next call
+    /// to `update` or `finalize`.
+    pub fn update<T: Extend<u8> + ?Sized>(
+        &mut self,
+        input: &[u8],
+        output: &mut T,
+    ) -> Result<(), Error> {
+        self.0.update(input, &mut ExtendOutput(output))
+    }
+
+    /// Decode the next chunk of input, writing the output to the beginning of
+    /// `output`. Returns the number of bytes written.
+    ///
+    /// Returns `FromBase64Error::BufferTooSmall` if `output` is shorter than
+    /// [`max_update_len`]`(input.len())`, in which case no input is consumed.
+    ///
+    /// [`max_update_len`]: Base64Decoder::max_update_len
+    pub fn update_to_slice(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+        if output.len() < self.0.max_update_len(input.len()) {
+            return Err(Error::BufferTooSmall);
+        }
+        write_to_slice(output, |out| self.0.update(input, out))
+    }
+
+    /// Decode any cached input and append it to `output`, then check that the
+    /// input was complete.
+    ///
+    /// The decoder is reset and can be used to decode a new input.
+    pub fn finalize<T: Extend<u8> + ?Sized>(&mut self, output: &mut T) -> Result<(), Error> {
+        self.0.finish(&mut ExtendOutput(output))
+    }
+
+    /// Decode any cached input and write it to the beginning of `output`,
+    /// then check that the input was complete. Returns the number of bytes
+    /// written.
+    ///
+    /// Returns `FromBase64Error::BufferTooSmall` if `output` is shorter than
+    /// [`max_finalize_len`]`()`, in which case the decoder is unchanged.
+    /// Otherwise, the decoder is reset and can be used to decode a new input.
+    ///
+    /// [`max_finalize_len`]: Base64Decoder::max_finalize_len
+    pub fn finalize_to_slice(&mut self, output: &mut [u8]) -> Result<usize, Error> {
+        if output.len() < self.0.max_finish_len() {
+            return Err(Error::BufferTooSmall);
+        }
+        write_to_slice(output, |out| self.0.finish(out))
+    }
+
+    /// The maximum number of bytes `update` may output for `input_len` bytes
+    /// of input.
+    pub fn max_update_len(&self, input_len: usize) -> usize {
+        self.0.max_update_len(input_len)
+    }
+
+    /// The maximum number of bytes `finalize` may output.
+    pub fn max_finalize_len(&self) -> usize {
+        self.0.max_finish_len()
+    }
+}
+
+impl Default for Base64Decoder {
+    fn default() -> Base64Decoder {
+        Base64Decoder::new()
     }
-    decode64(input, lut_align64::LutAlign64, Simple)
 }
 
 #[cfg(test)]
@@ -190,21 +868,31 @@ mod tests {
     use super::*;
 
     use crate::test_support::rand_base64_size;
-    use crate::ToBase64;
+    use crate::{DecodeConfig, ToBase64};
 
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     pub(super) fn test_avx2() -> avx2::Avx2 {
         unsafe { avx2::Avx2::new() }
     }
 
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub(super) fn test_ssse3() -> ssse3::Ssse3 {
+        unsafe { ssse3::Ssse3::new() }
+    }
+
     generate_tests![
         decoders<D>: {
             #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] avx2, test_avx2();
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] ssse3, test_ssse3();
             lut_align64, lut_align64::LutAlign64;
+            arithmetic, arithmetic::Arithmetic;
+            swar, swar::Swar;
         },
         packers<P>: {
             #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] avx2, test_avx2();
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] ssse3, test_ssse3();
             simple, Simple;
+            swar, swar::Swar;
         },
         tests: {
             decode,
@@ -214,6 +902,16 @@ mod tests {
             whitespace_skipped,
             all_bytes,
             wrapping_base64,
+            decode_to_slice,
+            decode_to_array,
+            decode_incremental,
+            decode_incremental_len,
+            trailer_position,
+            oblivious,
+            char_set,
+            canonical,
+            padding,
+            whitespace,
         },
     ];
 
@@ -237,7 +935,7 @@ mod tests {
         ];
 
         for (input, expected) in DECODE_TESTS {
-            let output = decode64(input, decoder, packer).unwrap();
+            let output = decode64(input, DecodeConfig::default(), decoder, packer).unwrap();
             if &output != expected {
                 panic!(
                     "Test failed. Expected specific output. \n\nInput: {}\nOutput: {:02x?}\nExpected output:{:02x?}\n\n",
@@ -246,6 +944,10 @@ mod tests {
                     expected
                 );
             }
+            assert_eq!(
+                try_decode64(input, DecodeConfig::default(), decoder, packer),
+                Ok(output)
+            );
         }
     }
 
@@ -256,8 +958,8 @@ mod tests {
         ];
 
         for (input1, input2) in DECODE_EQUIVALENCY_TESTS {
-            let output1 = decode64(input1, decoder, packer).unwrap();
-            let output2 = decode64(input2, decoder, packer).unwrap();
+            let output1 = decode64(input1, DecodeConfig::default(), decoder, packer).unwrap();
+            let output2 = decode64(input2, DecodeConfig::default(), decoder, packer).unwrap();
             if output1 != output2 {
                 panic!(
                     "Test failed. Expected same output.\n\nInput 1: {}\nInput 2: {}\nOutput 1: {:02x?}\nOutput 2:{:02x?}\n\n",
@@ -281,7 +983,12 @@ mod tests {
         ];
 
         for input in DECODE_ERROR_TESTS {
-            if decode64(input, decoder, packer).is_ok() {
+            let result = decode64(input, DecodeConfig::default(), decoder, packer);
+            assert_eq!(
+                try_decode64(input, DecodeConfig::default(), decoder, packer),
+                result
+            );
+            if result.is_ok() {
                 panic!(
                     "Test failed. Expected error.\n\nInput: {}\n\n",
                     std::str::from_utf8(input).unwrap(),
@@ -293,8 +1000,14 @@ mod tests {
     fn cmp_rand_1kb<D: Decoder, P: Packer>(decoder: D, packer: P) {
         let input = rand_base64_size(1024);
 
-        let output1 = decode64(&input, decoder, packer).unwrap();
-        let output2 = decode64(&input, lut_align64::LutAlign64, Simple).unwrap();
+        let output1 = decode64(&input, DecodeConfig::default(), decoder, packer).unwrap();
+        let output2 = decode64(
+            &input,
+            DecodeConfig::default(),
+            lut_align64::LutAlign64,
+            Simple,
+        )
+        .unwrap();
         if output1 != output2 {
             panic!(
                 "Test failed. Expected same output.\n\nInput: {}\nOutput 1: {:02x?}\nOutput 2:{:02x?}\n\n",
@@ -313,8 +1026,8 @@ mod tests {
             .flat_map(|&c| once(c).chain(once(b' ')))
             .collect::<Vec<_>>();
 
-        let output1 = decode64(&input1, decoder, packer).unwrap();
-        let output2 = decode64(&input2, decoder, packer).unwrap();
+        let output1 = decode64(&input1, DecodeConfig::default(), decoder, packer).unwrap();
+        let output2 = decode64(&input2, DecodeConfig::default(), decoder, packer).unwrap();
         if output1 != output2 {
             panic!(
                 "Test failed. Expected same output.\n\nInput 1: {}\nInput 2: {}\nOutput 1: {:02x?}\nOutput 2:{:02x?}\n\n",
@@ -339,12 +1052,18 @@ mod tests {
         set[b'\n' as usize] = Ok(None);
         set[b'\t' as usize] = Ok(None);
         set[b'\r' as usize] = Ok(None);
+        set[0x0b] = Ok(None);
         set[0x0c] = Ok(None);
 
         for (i, &expected) in set.iter().enumerate() {
-            let output = match decode64(&[i as u8, i as u8], decoder, packer)
-                .as_ref()
-                .map(|v| &v[..])
+            let output = match decode64(
+                &[i as u8, i as u8],
+                DecodeConfig::default(),
+                decoder,
+                packer,
+            )
+            .as_ref()
+            .map(|v| &v[..])
             {
                 Ok(&[]) => Ok(None),
                 Ok(&[v]) => Ok(Some(v >> 2)),
@@ -369,7 +1088,8 @@ mod tests {
         let bytes_per_line = BASE64_PEM_WRAP * 3 / 4;
         for _i in 0..(2 * bytes_per_line) {
             let encoded = v.to_base64(BASE64_PEM);
-            let decoded = decode64(encoded.as_bytes(), decoder, packer).unwrap();
+            let decoded =
+                decode64(encoded.as_bytes(), DecodeConfig::default(), decoder, packer).unwrap();
             assert_eq!(v, decoded);
             v.push(0);
         }
@@ -377,17 +1097,492 @@ mod tests {
         v = vec![];
         for _i in 0..1000 {
             let encoded = v.to_base64(BASE64_PEM);
-            let decoded = decode64(encoded.as_bytes(), decoder, packer).unwrap();
+            let decoded =
+                decode64(encoded.as_bytes(), DecodeConfig::default(), decoder, packer).unwrap();
             assert_eq!(v, decoded);
             v.push(rand::random::<u8>());
         }
     }
 
+    fn decode_to_slice<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        let input = rand_base64_size(1024);
+        let expected = decode64(&input, DecodeConfig::default(), decoder, packer).unwrap();
+
+        let mut output = [0u8; 1024];
+        let len = decode64_to_slice(
+            &input,
+            DecodeConfig::default(),
+            decoder,
+            packer,
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(&output[..len], &expected[..]);
+
+        let len = decode64_to_slice(
+            &input,
+            DecodeConfig::default(),
+            decoder,
+            packer,
+            &mut output[..expected.len()],
+        )
+        .unwrap();
+        assert_eq!(len, expected.len());
+
+        assert!(matches!(
+            decode64_to_slice(
+                &input,
+                DecodeConfig::default(),
+                decoder,
+                packer,
+                &mut output[..expected.len() - 1]
+            ),
+            Err(Error::BufferTooSmall)
+        ));
+        assert!(matches!(
+            decode64_to_slice(b"Zg==", DecodeConfig::default(), decoder, packer, &mut []),
+            Err(Error::BufferTooSmall)
+        ));
+
+        // Output written before an error is wiped
+        let mut invalid = input.clone();
+        invalid.extend_from_slice(b"$AAA");
+        let mut output = [0x55u8; 1024];
+        assert!(decode64_to_slice(
+            &invalid,
+            DecodeConfig::default(),
+            decoder,
+            packer,
+            &mut output
+        )
+        .is_err());
+        assert_eq!(output[0], 0);
+        assert!(output.iter().all(|&b| b == 0 || b == 0x55));
+    }
+
+    fn decode_to_array<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        let decode = |input: &[u8]| {
+            decode64_to_array::<_, _, 4>(input, DecodeConfig::default(), decoder, packer)
+        };
+
+        assert_eq!(decode(b"Zm9vYg=="), Ok(*b"foob"));
+        assert_eq!(decode(b"Zm9v\nYg"), Ok(*b"foob"));
+        assert_eq!(decode(b"Zm9vYmE="), Err(Error::WrongLength));
+        assert_eq!(decode(b"Zm9v"), Err(Error::WrongLength));
+        assert_eq!(decode(b""), Err(Error::WrongLength));
+        assert_eq!(decode(b"Zm9$Yg=="), Err(Error::InvalidCharacter(3)));
+        assert_eq!(decode(b"Zm9vYmFyZm9v$"), Err(Error::InvalidCharacter(12)));
+        assert_eq!(decode(b"Zm9vYmFyZm9vY"), Err(Error::InvalidLength));
+
+        let input = rand_base64_size(43);
+        let expected = decode64(&input, DecodeConfig::default(), decoder, packer).unwrap();
+        let output =
+            decode64_to_array::<_, _, 32>(&input, DecodeConfig::default(), decoder, packer);
+        assert_eq!(output.as_ref().map(|o| &o[..]), Ok(&expected[..]));
+    }
+
+    fn decode_incremental<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        use rand::{thread_rng, Rng};
+
+        fn decode_chunked<D: Decoder, P: Packer>(
+            input: &[u8],
+            decoder: D,
+            packer: P,
+        ) -> Result<Vec<u8>, Error> {
+            let mut rng = thread_rng();
+            let mut state = DecodeState::new(DecodeConfig::default(), decoder, packer);
+            let mut out = Vec::new();
+            let mut rest = input;
+            while !rest.is_empty() {
+                let (chunk, tail) = rest.split_at(rng.gen_range(1, rest.len() + 1));
+                state.update(chunk, &mut out)?;
+                rest = tail;
+            }
+            state.finish(&mut out)?;
+            Ok(out)
+        }
+
+        for len in 0..100 {
+            let input = rand_base64_size(len);
+            let expected = decode64(&input, DecodeConfig::default(), decoder, packer);
+            let output = decode_chunked(&input, decoder, packer);
+            assert_eq!(output, expected);
+        }
+
+        #[rustfmt::skip]
+        static TRAILER_TESTS: &[&[u8]] = &[
+            b"Zm9vYg  =  =  ",
+            b"Zm9vYg==\r\n",
+            b"Zm9vYmE=",
+            b"Zm9vYg=",
+            b"Zg==$",
+            b"Zm9vYmFy Zm9vYg= =Zm9v",
+            b"Zm9vYmFy Zm9vYg= = =",
+            b"Zm9vYmFyZm9vYmFyZm9vYmFyZm9vYmFyZm9vYmFy$",
+        ];
+
+        for input in TRAILER_TESTS {
+            let expected = decode64(input, DecodeConfig::default(), decoder, packer);
+            for _ in 0..20 {
+                let output = decode_chunked(input, decoder, packer);
+                assert_eq!(output, expected);
+            }
+        }
+    }
+
+    fn trailer_position<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        use rand::{thread_rng, RngCore};
+
+        let config = DecodeConfig::default();
+        for len in 0..100 {
+            let mut data = std::vec![0; len];
+            thread_rng().fill_bytes(&mut data);
+            let input = data.to_base64(crate::STANDARD).into_bytes();
+            assert_eq!(decode64(&input, config, decoder, packer), Ok(data));
+
+            // Data following the padding, or a third `=`
+            let extras: &[&[u8]] = match len % 3 {
+                0 => continue,
+                1 => &[b"A", b" \nA", b"="],
+                _ => &[b"A", b" \nA", b"==", b" = ="],
+            };
+            for extra in extras {
+                let mut input = input.clone();
+                input.extend_from_slice(extra);
+                assert_eq!(
+                    decode64(&input, config, decoder, packer),
+                    Err(Error::InvalidCharacter(input.len() - 1))
+                );
+            }
+        }
+    }
+
+    /// An operation performed while decoding, as recorded by `Tracing`.
+    #[derive(Debug, PartialEq)]
+    pub(super) enum Event {
+        /// `decode_block` was called. Its result isn't recorded, since which
+        /// characters of a block are valid depends on the position of
+        /// whitespace by definition.
+        DecodeBlock,
+        /// `compact_oblivious` was called on a block of the given length.
+        Compact(usize),
+        PackBlock,
+        /// `extend_from_slice` was called with the given number of bytes.
+        Extend(usize),
+    }
+
+    std::thread_local! {
+        static TRACE: core::cell::RefCell<Vec<Event>> = Default::default();
+    }
+
+    pub(super) fn trace(event: Event) {
+        TRACE.with(|t| t.borrow_mut().push(event));
+    }
+
+    /// Records the operations performed on the wrapped decoder, packer or
+    /// output in `TRACE`.
+    #[derive(Clone, Copy)]
+    struct Tracing<T>(T);
+
+    impl<D: Decoder> Decoder for Tracing<D> {
+        type Block = D::Block;
+
+        fn decode_block(self, block: &mut Self::Block, config: DecodeConfig) -> BlockResult {
+            trace(Event::DecodeBlock);
+            self.0.decode_block(block, config)
+        }
+
+        fn zero_block() -> Self::Block {
+            D::zero_block()
+        }
+    }
+
+    impl<P: Packer> Packer for Tracing<P> {
+        type Input = P::Input;
+        type Output = P::Output;
+
+        fn pack_block(self, input: &Self::Input, output: &mut Self::Output) {
+            trace(Event::PackBlock);
+            self.0.pack_block(input, output)
+        }
+    }
+
+    impl Output for Tracing<Vec<u8>> {
+        fn extend_from_slice(&mut self, data: &[u8]) -> Result<(), Error> {
+            trace(Event::Extend(data.len()));
+            Output::extend_from_slice(&mut self.0, data)
+        }
+
+        fn extend_oblivious(&mut self, data: &[u8], len: usize) -> Result<(), Error> {
+            trace(Event::Extend(data.len()));
+            self.0.extend_oblivious(data, len)
+        }
+    }
+
+    /// Decodes inputs that only differ in the position of whitespace, which
+    /// moves between blocks, and checks that the traces are identical.
+    fn oblivious<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        use rand::seq::SliceRandom;
+        use rand::thread_rng;
+
+        let mut rng = thread_rng();
+        let config = DecodeConfig {
+            oblivious: true,
+            ..Default::default()
+        };
+        let data = rand_base64_size(120);
+        let expected = decode64(&data, DecodeConfig::default(), decoder, packer).unwrap();
+
+        let block_len = core::mem::size_of::<D::Block>();
+        TRACE.with(|t| t.take());
+        let mut first_trace = None;
+        for _ in 0..20 {
+            let mut is_space = std::vec![false; data.len()];
+            is_space.resize(data.len() + 40, true);
+            is_space.shuffle(&mut rng);
+            let mut chars = data.iter();
+            let input: Vec<u8> = is_space
+                .into_iter()
+                .map(|space| if space { b' ' } else { *chars.next().unwrap() })
+                .collect();
+
+            let mut output = Tracing(Vec::new());
+            decode64_to(
+                &input,
+                config,
+                Tracing(decoder),
+                Tracing(packer),
+                &mut output,
+            )
+            .unwrap();
+            assert_eq!(output.0, expected);
+
+            let trace = TRACE.with(|t| t.take());
+            if block_len > 1 {
+                let decoded = trace.iter().filter(|e| **e == Event::DecodeBlock).count();
+                let compacted = trace
+                    .iter()
+                    .filter(|e| **e == Event::Compact(block_len))
+                    .count();
+                assert_eq!(decoded, compacted);
+            }
+            match first_trace {
+                None => first_trace = Some(trace),
+                Some(ref first_trace) => assert_eq!(*first_trace, trace),
+            }
+
+            let mut output = std::vec![0; expected.len()];
+            let len = decode64_to_slice(&input, config, decoder, packer, &mut output).unwrap();
+            assert_eq!(&output[..len], &expected[..]);
+            TRACE.with(|t| t.take());
+        }
+    }
+
+    #[test]
+    fn compact_oblivious() {
+        use rand::{thread_rng, Rng, RngCore};
+
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let len = rng.gen_range(1, 33);
+            let mut block = std::vec![0; len];
+            rng.fill_bytes(&mut block);
+            let valid = rng.next_u32() & (u32::MAX >> (32 - len));
+
+            let expected = (0..len)
+                .filter(|&i| (valid >> i) & 1 == 1)
+                .map(|i| block[i])
+                .collect::<Vec<_>>();
+            super::compact_oblivious(&mut block, valid);
+            assert_eq!(block[..expected.len()], expected[..]);
+        }
+    }
+
+    fn decode_incremental_len<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+        let input = rand_base64_size(1024);
+        let mut state = DecodeState::new(DecodeConfig::default(), decoder, packer);
+        let mut output = Vec::new();
+        let mut rest = &input[..];
+        while !rest.is_empty() {
+            let (chunk, tail) = rest.split_at(rng.gen_range(0, rest.len().min(40) + 1));
+            let max = state.max_update_len(chunk.len());
+            let start = output.len();
+            state.update(chunk, &mut output).unwrap();
+            assert!(output.len() - start <= max);
+            rest = tail;
+        }
+        let max = state.max_finish_len();
+        let start = output.len();
+        state.finish(&mut output).unwrap();
+        assert!(output.len() - start <= max);
+        assert_eq!(
+            output,
+            decode64(&input, DecodeConfig::default(), decoder, packer).unwrap()
+        );
+    }
+
+    fn char_set<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        use crate::CharacterSet::*;
+
+        #[rustfmt::skip]
+        static CHAR_SET_TESTS: &[(Option<crate::CharacterSet>, &[u8], &[u8])] = &[
+            (None, b"+/-_", b""),
+            (Some(Standard), b"+/", b""),
+            (Some(UrlSafe), b"-_", b""),
+            (Some(Standard), b"", b"-_"),
+            (Some(UrlSafe), b"", b"+/"),
+        ];
+
+        fn rand_alnum_64() -> Vec<u8> {
+            let mut input = rand_base64_size(64);
+            for c in &mut input {
+                if !c.is_ascii_alphanumeric() {
+                    *c = b'A';
+                }
+            }
+            input
+        }
+
+        for &(char_set, accepted, rejected) in CHAR_SET_TESTS {
+            let config = DecodeConfig {
+                char_set,
+                ..Default::default()
+            };
+            for &c in accepted {
+                let mut input = rand_alnum_64();
+                input[37] = c;
+                assert!(decode64(&input, config, decoder, packer).is_ok());
+            }
+            for &c in rejected {
+                let mut input = rand_alnum_64();
+                input[37] = c;
+                assert_eq!(
+                    decode64(&input, config, decoder, packer),
+                    Err(Error::InvalidCharacter(37))
+                );
+            }
+        }
+    }
+
+    fn canonical<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        type Expected = Result<&'static [u8], Error>;
+
+        #[rustfmt::skip]
+        static CANONICAL_TESTS: &[(&[u8], Expected)] = &[
+            (b"Zg==", Ok(b"f")),
+            (b"Zh==", Err(Error::NonCanonical(1))),
+            (b"Zm8=", Ok(b"fo")),
+            (b"Zm9=", Err(Error::NonCanonical(2))),
+            (b"Zm9v", Ok(b"foo")),
+            (b"Zg", Ok(b"f")),
+            (b"Zh\n", Err(Error::NonCanonical(1))),
+            (b"Zm9vYmFy Zm9vYmFy Zm9vYmFy Zm9vYmFy Zm9vYmFy Zr  ", Err(Error::NonCanonical(46))),
+            (b"Z", Err(Error::InvalidLength)),
+            (b"Zh=", Err(Error::InvalidTrailer)),
+        ];
+
+        let config = DecodeConfig {
+            canonical: true,
+            ..Default::default()
+        };
+        for (input, expected) in CANONICAL_TESTS {
+            let output = decode64(input, config, decoder, packer);
+            assert_eq!(output.as_deref().map_err(|e| *e), *expected);
+            if let Err(Error::NonCanonical(_)) = expected {
+                assert!(decode64(input, DecodeConfig::default(), decoder, packer).is_ok());
+            }
+        }
+
+        let config = DecodeConfig {
+            padding: crate::Padding::Required,
+            ..config
+        };
+        assert_eq!(
+            decode64(b"Zh", config, decoder, packer),
+            Err(Error::MissingPadding)
+        );
+    }
+
+    fn padding<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        use crate::Padding::*;
+
+        type Expected = Result<&'static [u8], Error>;
+
+        #[rustfmt::skip]
+        static PADDING_TESTS: &[(crate::Padding, &[u8], Expected)] = &[
+            (Indifferent, b"Zg==", Ok(b"f")),
+            (Indifferent, b"Zg", Ok(b"f")),
+            (Required, b"", Ok(b"")),
+            (Required, b"Zg==", Ok(b"f")),
+            (Required, b"Zm8=", Ok(b"fo")),
+            (Required, b"Zm9v", Ok(b"foo")),
+            (Required, b"Zg", Err(Error::MissingPadding)),
+            (Required, b"Zm8\n", Err(Error::MissingPadding)),
+            (Required, b"Zg=", Err(Error::InvalidTrailer)),
+            (Required, b"Z", Err(Error::InvalidLength)),
+            (Forbidden, b"", Ok(b"")),
+            (Forbidden, b"Zg", Ok(b"f")),
+            (Forbidden, b"Zm8", Ok(b"fo")),
+            (Forbidden, b"Zm9v", Ok(b"foo")),
+            (Forbidden, b"Zg==", Err(Error::UnexpectedPadding(2))),
+            (Forbidden, b"Zm9vYmFy Zm9vYmFy Zm9vYmFy Zm9vYmFy Zm9vYmFy Zm8=", Err(Error::UnexpectedPadding(48))),
+        ];
+
+        for &(padding, input, expected) in PADDING_TESTS {
+            let config = DecodeConfig {
+                padding,
+                ..Default::default()
+            };
+            let output = decode64(input, config, decoder, packer);
+            assert_eq!(output.as_deref().map_err(|e| *e), expected);
+        }
+    }
+
+    fn whitespace<D: Decoder, P: Packer>(decoder: D, packer: P) {
+        use crate::Whitespace::*;
+
+        type Expected = Result<&'static [u8], Error>;
+
+        #[rustfmt::skip]
+        static WHITESPACE_TESTS: &[(crate::Whitespace, &[u8], Expected)] = &[
+            (Any, b"Zm9v Ym\tFy\r\n\x0b\x0cZm8=\n", Ok(b"foobarfo")),
+            (LineBreaks, b"Zm9vYmFy\r\nZm9vYmFy\nZm8=\r\n", Ok(b"foobarfoobarfo")),
+            (LineBreaks, b"Zm9v YmFy", Err(Error::InvalidCharacter(4))),
+            (LineBreaks, b"Zm9vYmFy\tZm8=", Err(Error::InvalidCharacter(8))),
+            (LineBreaks, b"Zm9vYmFy\x0bZm8=", Err(Error::InvalidCharacter(8))),
+            (LineBreaks, b"Zm9vYmFyZm9vYmFyZm9vYmFyZm9vYmFyZm9vYmFy\x0c", Err(Error::InvalidCharacter(40))),
+            (LineBreaks, b"Zg=\n= ", Err(Error::InvalidCharacter(5))),
+            (Forbidden, b"Zm9vYmFy", Ok(b"foobar")),
+            (Forbidden, b"Zm9vYmFy\n", Err(Error::InvalidCharacter(8))),
+            (Forbidden, b"Zm9vYmFyZm9vYmFyZm9vYmFyZm9vYmFyZm9vYmFy\r", Err(Error::InvalidCharacter(40))),
+            (Forbidden, b"Zg= =", Err(Error::InvalidCharacter(3))),
+            (Forbidden, b"Zg==\n", Err(Error::InvalidCharacter(4))),
+        ];
+
+        for &(whitespace, input, expected) in WHITESPACE_TESTS {
+            let config = DecodeConfig {
+                whitespace,
+                ..Default::default()
+            };
+            let output = decode64(input, config, decoder, packer);
+            assert_eq!(output.as_deref().map_err(|e| *e), expected);
+        }
+    }
+
     #[test]
     fn display_errors() {
         println!("Invalid length is {}", Error::InvalidLength);
         println!("Invalid trailer is {}", Error::InvalidTrailer);
         println!("Invalid character is {}", Error::InvalidCharacter(0));
+        println!("Buffer too small is {}", Error::BufferTooSmall);
+        println!("Non-canonical is {}", Error::NonCanonical(0));
+        println!("Missing padding is {}", Error::MissingPadding);
+        println!("Unexpected padding is {}", Error::UnexpectedPadding(0));
+        println!("Allocation failed is {}", Error::AllocationFailed);
+        println!("Wrong length is {}", Error::WrongLength);
     }
 }
 
@@ -395,10 +1590,14 @@ mod tests {
 mod benches {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     use super::tests::test_avx2;
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    use super::tests::test_ssse3;
     use super::*;
 
     use test::Bencher;
 
+    use crate::DecodeConfig;
+
     use crate::test_support::rand_base64_size;
 
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
@@ -406,7 +1605,18 @@ mod benches {
     fn avx2_1mb(b: &mut Bencher) {
         let input = rand_base64_size(1024 * 1024);
         b.iter(|| {
-            let ret = decode64(&input, test_avx2(), test_avx2()).unwrap();
+            let ret = decode64(&input, DecodeConfig::default(), test_avx2(), test_avx2()).unwrap();
+            std::hint::black_box(ret);
+        });
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[bench]
+    fn ssse3_1mb(b: &mut Bencher) {
+        let input = rand_base64_size(1024 * 1024);
+        b.iter(|| {
+            let ret =
+                decode64(&input, DecodeConfig::default(), test_ssse3(), test_ssse3()).unwrap();
             std::hint::black_box(ret);
         });
     }
@@ -415,7 +1625,37 @@ mod benches {
     fn lut_align64_1mb(b: &mut Bencher) {
         let input = rand_base64_size(1024 * 1024);
         b.iter(|| {
-            let ret = decode64(&input, lut_align64::LutAlign64, Simple).unwrap();
+            let ret = decode64(
+                &input,
+                DecodeConfig::default(),
+                lut_align64::LutAlign64,
+                Simple,
+            )
+            .unwrap();
+            std::hint::black_box(ret);
+        });
+    }
+
+    #[bench]
+    fn arithmetic_1mb(b: &mut Bencher) {
+        let input = rand_base64_size(1024 * 1024);
+        b.iter(|| {
+            let ret = decode64(
+                &input,
+                DecodeConfig::default(),
+                arithmetic::Arithmetic,
+                Simple,
+            )
+            .unwrap();
+            std::hint::black_box(ret);
+        });
+    }
+
+    #[bench]
+    fn swar_1mb(b: &mut Bencher) {
+        let input = rand_base64_size(1024 * 1024);
+        b.iter(|| {
+            let ret = decode64(&input, DecodeConfig::default(), swar::Swar, swar::Swar).unwrap();
             std::hint::black_box(ret);
         });
     }
@@ -425,7 +1665,18 @@ mod benches {
     fn avx2_1kb(b: &mut Bencher) {
         let input = rand_base64_size(1024);
         b.iter(|| {
-            let ret = decode64(&input, test_avx2(), test_avx2()).unwrap();
+            let ret = decode64(&input, DecodeConfig::default(), test_avx2(), test_avx2()).unwrap();
+            std::hint::black_box(ret);
+        });
+    }
+
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[bench]
+    fn ssse3_1kb(b: &mut Bencher) {
+        let input = rand_base64_size(1024);
+        b.iter(|| {
+            let ret =
+                decode64(&input, DecodeConfig::default(), test_ssse3(), test_ssse3()).unwrap();
             std::hint::black_box(ret);
         });
     }
@@ -434,7 +1685,37 @@ mod benches {
     fn lut_align64_1kb(b: &mut Bencher) {
         let input = rand_base64_size(1024);
         b.iter(|| {
-            let ret = decode64(&input, lut_align64::LutAlign64, Simple).unwrap();
+            let ret = decode64(
+                &input,
+                DecodeConfig::default(),
+                lut_align64::LutAlign64,
+                Simple,
+            )
+            .unwrap();
+            std::hint::black_box(ret);
+        });
+    }
+
+    #[bench]
+    fn arithmetic_1kb(b: &mut Bencher) {
+        let input = rand_base64_size(1024);
+        b.iter(|| {
+            let ret = decode64(
+                &input,
+                DecodeConfig::default(),
+                arithmetic::Arithmetic,
+                Simple,
+            )
+            .unwrap();
+            std::hint::black_box(ret);
+        });
+    }
+
+    #[bench]
+    fn swar_1kb(b: &mut Bencher) {
+        let input = rand_base64_size(1024);
+        b.iter(|| {
+            let ret = decode64(&input, DecodeConfig::default(), swar::Swar, swar::Swar).unwrap();
             std::hint::black_box(ret);
         });
     }
@@ -0,0 +1,201 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::io::{self, Read};
+use std::vec::Vec;
+
+use super::{ArchDecoder, Error};
+use crate::misc::{wipe, Scratch};
+
+/// The number of bytes of base64 text read from the inner reader at a time.
+const INPUT_BUF_LEN: usize = 4096;
+
+/// A reader that decodes base64 text read from an inner reader.
+///
+/// The accepted input is identical to that of
+/// [`decode_config`].
+/// Decoding errors are returned as an [`io::Error`] of kind
+/// [`InvalidData`], wrapping a [`FromBase64Error`]. The index in
+/// `FromBase64Error::InvalidCharacter` is relative to the start of the
+/// base64 text. Once an error has been returned, all subsequent reads return
+/// the same error.
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::Read;
+/// use b64_ct::DecoderReader;
+///
+/// let mut reader = DecoderReader::new(&b"SGVsbG8s\nIFdvcmxk"[..]);
+/// let mut output = Vec::new();
+/// reader.read_to_end(&mut output).unwrap();
+/// assert_eq!(output, b"Hello, World");
+/// ```
+///
+/// [`decode_config`]: crate::decode_config
+/// [`FromBase64Error`]: crate::FromBase64Error
+/// [`InvalidData`]: io::ErrorKind::InvalidData
+pub struct DecoderReader<R: Read> {
+    inner: R,
+    decoder: ArchDecoder,
+    input: Scratch<Vec<u8>>,
+    output: Scratch<Vec<u8>>,
+    output_pos: usize,
+    finished: bool,
+    error: Option<Error>,
+}
+
+impl<R: Read> DecoderReader<R> {
+    /// Create a new decoder that reads base64 text from `inner`.
+    pub fn new(inner: R) -> DecoderReader<R> {
+        DecoderReader::with_config(inner, crate::DecodeConfig::default())
+    }
+
+    /// Create a new decoder that reads base64 text from `inner`, following
+    /// the specified decoding configuration.
+    pub fn with_config(inner: R, config: crate::DecodeConfig) -> DecoderReader<R> {
+        DecoderReader {
+            inner,
+            decoder: ArchDecoder::new(config),
+            input: Scratch(std::vec![0; INPUT_BUF_LEN]),
+            output: Scratch(Vec::new()),
+            output_pos: 0,
+            finished: false,
+            error: None,
+        }
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader.
+    ///
+    /// Reading directly from the inner reader may corrupt the decoded output.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Return the inner reader. Any buffered data is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read and decode the next chunk of input into the output buffer.
+    ///
+    /// The output buffer is wiped before it is reused, and reserved up front
+    /// so decoded data is never left behind in a reallocated buffer.
+    fn fill_output(&mut self) -> io::Result<()> {
+        wipe(&mut self.output);
+        self.output.clear();
+        self.output_pos = 0;
+
+        let n = self.inner.read(&mut self.input)?;
+        let result = if n == 0 {
+            self.finished = true;
+            self.output.reserve(self.decoder.max_finish_len());
+            self.decoder.finish(&mut *self.output)
+        } else {
+            self.output.reserve(self.decoder.max_update_len(n));
+            self.decoder.update(&self.input[..n], &mut *self.output)
+        };
+
+        if let Err(e) = result {
+            wipe(&mut self.output);
+            self.output.clear();
+            self.error = Some(e);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecoderReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.output_pos == self.output.len() {
+            if let Some(e) = self.error {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+            if self.finished || buf.is_empty() {
+                return Ok(0);
+            }
+            self.fill_output()?;
+        }
+
+        let output = &self.output[self.output_pos..];
+        let n = output.len().min(buf.len());
+        buf[..n].copy_from_slice(&output[..n]);
+        self.output_pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{thread_rng, Rng, RngCore};
+
+    use crate::{FromBase64, ToBase64, MIME, STANDARD};
+
+    /// A reader that returns a random number of bytes per call.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = thread_rng().gen_range(0, self.0.len().min(buf.len()) + 1);
+            if n == 0 && !self.0.is_empty() && !buf.is_empty() {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    fn decode_all(input: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut output = Vec::new();
+        match DecoderReader::new(Trickle(input)).read_to_end(&mut output) {
+            Ok(_) => Ok(output),
+            Err(e) => Err(*e.into_inner().unwrap().downcast::<Error>().unwrap()),
+        }
+    }
+
+    #[test]
+    fn same_as_from_base64() {
+        let mut rng = thread_rng();
+        for len in (0..100).chain([1000, 4096, 10000].iter().cloned()) {
+            let mut data = std::vec![0; len];
+            rng.fill_bytes(&mut data);
+            for config in &[STANDARD, MIME] {
+                let input = data.to_base64(*config);
+                assert_eq!(decode_all(input.as_bytes()).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut input = std::vec![b'A'; 10000];
+        input[5000] = b'$';
+        assert_eq!(decode_all(&input), Err(Error::InvalidCharacter(5000)));
+        assert_eq!(decode_all(&input), input.from_base64());
+
+        let mut input = std::vec![b'A'; 9000];
+        input.extend_from_slice(b"AA==\n\n=");
+        assert_eq!(decode_all(&input), Err(Error::InvalidCharacter(9006)));
+
+        assert_eq!(decode_all(b"AAAAA"), Err(Error::InvalidLength));
+    }
+
+    #[test]
+    fn sticky_error() {
+        let mut reader = DecoderReader::new(&b"AA$A"[..]);
+        let mut buf = [0; 16];
+        assert!(reader.read(&mut buf).is_err());
+        assert!(reader.read(&mut buf).is_err());
+    }
+}
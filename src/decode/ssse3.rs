@@ -0,0 +1,225 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use crate::misc::Scratch;
+use crate::ssse3::*;
+
+/// # Safety
+/// The caller should ensure the requisite CPU features are enabled.
+#[target_feature(enable = "ssse3,sse4.1")]
+unsafe fn cmpeq_any(input: __m128i, chars: &[u8]) -> __m128i {
+    let mut mask = _mm_setzero_si128();
+    for &c in chars {
+        mask = _mm_or_si128(mask, _mm_cmpeq_epi8(input, _mm_set1_epi8(c as _)));
+    }
+    mask
+}
+
+/// # Safety
+/// The caller should ensure the requisite CPU features are enabled.
+#[target_feature(enable = "ssse3,sse4.1")]
+unsafe fn decode_ssse3(input: __m128i, config: crate::DecodeConfig) -> (__m128i, u32, u32, u32) {
+    // This is the same algorithm as in the AVX2 implementation, see there for
+    // details. Only step 4 differs, because PCMPESTRM isn't available.
+
+    // Step 0. Split input bytes into nibbles.
+    let higher_nibble = _mm_and_si128(_mm_srli_epi16(input, 4), _mm_set1_epi8(0x0f));
+    let lower_nibble = _mm_and_si128(input, _mm_set1_epi8(0x0f));
+
+    // Step 1. Find invalid characters.
+    #[rustfmt::skip]
+    let row_lut = mm_setr_epu8([
+        0b1010_1100, 0b1111_1000, 0b1111_1000, 0b1111_1000,
+        0b1111_1000, 0b1111_1000, 0b1111_1000, 0b1111_1000,
+        0b1111_1000, 0b1111_1001, 0b1111_0001, 0b0101_0101,
+        0b0101_0001, 0b0101_1101, 0b0101_0000, 0b0111_0100,
+    ]);
+
+    #[rustfmt::skip]
+    let column_lut = mm_setr_epu8([
+        0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80,
+           0,    0,    0,    0,    0,    0,    0,    0,
+    ]);
+
+    let row = _mm_shuffle_epi8(row_lut, lower_nibble);
+    let column = _mm_shuffle_epi8(column_lut, higher_nibble);
+    let valid = _mm_and_si128(row, column);
+    let non_match = _mm_cmpeq_epi8(valid, _mm_setzero_si128());
+    let rejected_chars: &[u8] = match config.char_set {
+        Some(crate::Standard) => b"-_",
+        Some(crate::UrlSafe) => b"+/",
+        None => b"",
+    };
+    let rejected_space: &[u8] = match config.whitespace {
+        crate::Whitespace::Forbidden => b" \t\n\x0b\x0c\r",
+        crate::Whitespace::LineBreaks => b" \t\x0b\x0c",
+        crate::Whitespace::Any => b"",
+    };
+    let rejected = _mm_or_si128(
+        cmpeq_any(input, rejected_chars),
+        cmpeq_any(input, rejected_space),
+    );
+    let invalid_mask = _mm_movemask_epi8(_mm_or_si128(non_match, rejected));
+
+    // Step 2. Numbers & letters.
+    #[rustfmt::skip]
+    let shift_lut = mm_setr_epu8([
+        0, 0, 0,
+        // '0' through '9'
+        4,
+        // 'A' through 'Z'
+        -65i8 as u8, -65i8 as u8,
+        // 'a' through 'z'
+        -71i8 as u8, -71i8 as u8,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    ]);
+
+    let shift = _mm_shuffle_epi8(shift_lut, higher_nibble);
+    let shifted = _mm_add_epi8(input, shift);
+
+    // Step 3. Special characters.
+    #[rustfmt::skip]
+    let spcrange_lut = mm_setr_epu8([
+        0, 0, 0xff, 0, 0, 0xff, 0, 0,
+        0, 0,    0, 0, 0,    0, 0, 0,
+    ]);
+
+    #[rustfmt::skip]
+    let spcchar_lut = mm_setr_epu8([
+        0,   0,   0,   0, 0,   0, 0, 0,
+        // '+', '_', '-',    '/'
+        0, !62, !63, !62, 0, !63, 0, 0,
+    ]);
+
+    let sel_range = _mm_shuffle_epi8(spcrange_lut, higher_nibble);
+    let lo_sub_hi = _mm_sub_epi8(lower_nibble, higher_nibble);
+    let specials = _mm_shuffle_epi8(spcchar_lut, lo_sub_hi);
+    let sel_spec = _mm_and_si128(sel_range, specials);
+
+    let result = _mm_blendv_epi8(shifted, _mm_not_si128(specials), sel_spec);
+
+    // Step 4. Compute mask for valid non-whitespace bytes: everything that
+    // matched in step 1, except whitespace and padding.
+    let skipped = cmpeq_any(input, b" \t\n\x0b\x0c\r=");
+    let valid_mask = _mm_movemask_epi8(_mm_not_si128(_mm_or_si128(non_match, skipped)));
+
+    // Step 5. Compute mask for padding characters.
+    let pad_mask = _mm_movemask_epi8(_mm_cmpeq_epi8(input, _mm_set1_epi8(b'=' as _)));
+
+    (result, invalid_mask as _, valid_mask as _, pad_mask as _)
+}
+
+/// # Safety
+/// The caller should ensure the requisite CPU features are enabled.
+#[target_feature(enable = "ssse3,sse4.1")]
+unsafe fn decode_block(
+    block: &mut <Ssse3 as super::Decoder>::Block,
+    config: crate::DecodeConfig,
+) -> super::BlockResult {
+    let input = array_as_m128i(*block);
+
+    let (unpacked, invalid, valid, pad) = decode_ssse3(input, config);
+
+    // The decoded sextets are wiped when dropped
+    let unpacked = Scratch(m128i_as_array(unpacked));
+
+    let out_length = valid.count_ones() as _;
+
+    if config.oblivious {
+        *block = *unpacked;
+        super::compact_oblivious(block, valid);
+    } else {
+        let mut valid_mask = valid;
+        let mut out_iter = block.iter_mut();
+        for &val in unpacked.iter() {
+            if (valid_mask & 1) == 1 {
+                *out_iter.next().unwrap() = val;
+            }
+            valid_mask >>= 1;
+        }
+    }
+
+    super::BlockResult {
+        out_length,
+        valid,
+        invalid,
+        pad,
+    }
+}
+
+/// # Safety
+/// The caller should ensure the requisite CPU features are enabled.
+#[target_feature(enable = "ssse3,sse4.1")]
+unsafe fn pack_block(
+    input: &<Ssse3 as super::Packer>::Input,
+    output: &mut <Ssse3 as super::Packer>::Output,
+) {
+    let unpacked = array_as_m128i(*input);
+
+    // Pack 16× 6-bit values into 8× 12-bit values
+    let packed1 = _mm_maddubs_epi16(unpacked, _mm_set1_epi16(0x0140));
+    // Pack 8× 12-bit values into 4× 3-byte values
+    let packed2 = _mm_madd_epi16(packed1, _mm_set1_epi32(0x00011000));
+    // Pack 4× 3-byte values into 1× 12-byte value
+    #[rustfmt::skip]
+    let packed3 = _mm_shuffle_epi8(packed2, mm_setr_epu8([
+           2,  1,  0,
+           6,  5,  4,
+          10,  9,  8,
+          14, 13, 12,
+          0xff, 0xff, 0xff, 0xff,
+    ]));
+
+    *output = m128i_as_array(packed3);
+}
+
+#[derive(Copy, Clone)]
+pub(super) struct Ssse3 {
+    _private: (),
+}
+
+impl Ssse3 {
+    /// # Safety
+    /// The caller should ensure the requisite CPU features are enabled.
+    #[target_feature(enable = "ssse3,sse4.1")]
+    pub(super) unsafe fn new() -> Ssse3 {
+        Ssse3 { _private: () }
+    }
+}
+
+impl super::Decoder for Ssse3 {
+    type Block = [u8; 16];
+
+    #[inline]
+    fn decode_block(
+        self,
+        block: &mut Self::Block,
+        config: crate::DecodeConfig,
+    ) -> super::BlockResult {
+        // safe: `self` was given as a witness that the features are available
+        unsafe { decode_block(block, config) }
+    }
+
+    #[inline(always)]
+    fn zero_block() -> Self::Block {
+        [b' '; 16]
+    }
+}
+
+impl super::Packer for Ssse3 {
+    type Input = [u8; 16];
+    type Output = [u8; 16];
+
+    fn pack_block(self, input: &Self::Input, output: &mut Self::Output) {
+        // safe: `self` was given as a witness that the features are available
+        unsafe { pack_block(input, output) }
+    }
+}
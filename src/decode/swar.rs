@@ -0,0 +1,92 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::swar::{add, eq, in_range, splat, to_bitmask};
+
+#[derive(Copy, Clone)]
+pub(super) struct Swar;
+
+impl super::Decoder for Swar {
+    type Block = [u8; 8];
+
+    #[inline]
+    fn decode_block(
+        self,
+        block: &mut Self::Block,
+        config: crate::DecodeConfig,
+    ) -> super::BlockResult {
+        // Same as `Arithmetic`, for 8 characters at a time.
+        let b = u64::from_le_bytes(*block);
+
+        let upper = in_range(b, b'A', b'Z');
+        let lower = in_range(b, b'a', b'z');
+        let digit = in_range(b, b'0', b'9');
+        let (plus, slash) = (eq(b, b'+'), eq(b, b'/'));
+        let (minus, underscore) = (eq(b, b'-'), eq(b, b'_'));
+        let line_break = eq(b, b'\n') | eq(b, b'\r');
+        let other_space = eq(b, b' ') | eq(b, b'\t') | eq(b, 0x0b) | eq(b, 0x0c);
+        let pad = eq(b, b'=');
+
+        let value = (upper & add(b, splat(0u8.wrapping_sub(b'A'))))
+            | (lower & add(b, splat(0u8.wrapping_sub(b'a' - 26))))
+            | (digit & add(b, splat(52 - b'0')))
+            | ((plus | minus) & splat(62))
+            | ((slash | underscore) & splat(63));
+
+        let rejected = match config.char_set {
+            Some(crate::Standard) => minus | underscore,
+            Some(crate::UrlSafe) => plus | slash,
+            None => 0,
+        } | match config.whitespace {
+            crate::Whitespace::Forbidden => line_break | other_space,
+            crate::Whitespace::LineBreaks => other_space,
+            crate::Whitespace::Any => 0,
+        };
+        let accepted = upper | lower | digit | plus | slash | minus | underscore;
+        let valid = to_bitmask(accepted & !rejected);
+        let invalid = to_bitmask(!(accepted | line_break | other_space | pad) | rejected);
+
+        *block = value.to_le_bytes();
+        if config.oblivious {
+            super::compact_oblivious(block, valid);
+        } else {
+            let mut out = 0;
+            for i in 0..8 {
+                if (valid >> i) & 1 == 1 {
+                    block[out] = block[i];
+                    out += 1;
+                }
+            }
+        }
+
+        super::BlockResult {
+            out_length: valid.count_ones() as _,
+            valid,
+            invalid,
+            pad: to_bitmask(pad),
+        }
+    }
+
+    #[inline(always)]
+    fn zero_block() -> Self::Block {
+        [b' '; 8]
+    }
+}
+
+impl super::Packer for Swar {
+    type Input = [u8; 8];
+    type Output = [u8; 6];
+
+    fn pack_block(self, input: &Self::Input, output: &mut Self::Output) {
+        let x = u64::from_le_bytes(*input);
+        // Join pairs of 6-bit values into 12-bit values in 16-bit lanes
+        let x = ((x & 0x00ff_00ff_00ff_00ff) << 6) | ((x >> 8) & 0x00ff_00ff_00ff_00ff);
+        // Join pairs of 12-bit values into 24-bit values in 32-bit lanes
+        let x = ((x & 0x0000_ffff_0000_ffff) << 12) | ((x >> 16) & 0x0000_ffff_0000_ffff);
+        output[..3].copy_from_slice(&(x as u32).to_be_bytes()[1..]);
+        output[3..].copy_from_slice(&((x >> 32) as u32).to_be_bytes()[1..]);
+    }
+}
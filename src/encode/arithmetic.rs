@@ -0,0 +1,35 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::arithmetic::gt;
+
+#[derive(Copy, Clone)]
+pub(super) struct Arithmetic;
+
+impl super::Encoder for Arithmetic {
+    type Block = [u8; 1];
+
+    fn encode_block(self, block: &mut Self::Block, charset: crate::CharacterSet) {
+        let (c62, c63) = match charset {
+            crate::Standard => (b'+', b'/'),
+            crate::UrlSafe => (b'-', b'_'),
+        };
+
+        // Start from 'A' and adjust the offset for every range the value is
+        // past.
+        let v = block[0];
+        let mut c = v.wrapping_add(b'A');
+        // 'a' through 'z'
+        c = c.wrapping_add(gt(v, 25) & (b'a' - b'A' - 26));
+        // '0' through '9'
+        c = c.wrapping_add(gt(v, 51) & b'0'.wrapping_sub(b'a' + 26));
+        // 62
+        c = c.wrapping_add(gt(v, 61) & c62.wrapping_sub(b'9' + 1));
+        // 63
+        c = c.wrapping_add(gt(v, 62) & c63.wrapping_sub(c62 + 1));
+        block[0] = c;
+    }
+}
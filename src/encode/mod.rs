@@ -4,13 +4,46 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+mod arithmetic;
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 mod avx2;
 mod lut_align64;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod ssse3;
+mod swar;
+#[cfg(feature = "std")]
+mod write;
+
+#[cfg(feature = "std")]
+pub use self::write::EncoderWriter;
 
 use alloc::{string::String, vec::Vec};
+use core::cmp;
+use core::fmt;
 use core::mem::size_of;
 
+use crate::misc::{wipe, Scratch};
+
+/// Errors that can occur when encoding into a caller-provided buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The output buffer is too small to hold the encoded output.
+    BufferTooSmall,
+    /// Memory for the output couldn't be allocated. Only returned by the
+    /// `try_` functions.
+    AllocationFailed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self, f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 trait Encoder: Copy {
     type Block: AsRef<[u8]> + AsMut<[u8]> + Default;
 
@@ -31,6 +64,7 @@ impl Unpacker for Simple {
     type Input = [u8; 3];
     type Output = [u8; 4];
 
+    #[allow(clippy::identity_op)]
     fn unpack_block(self, input: &Self::Input, output: &mut Self::Output) {
         output[0] = input[0] >> 2;
         output[1] = ((input[0] & 0x03) << 4) | (input[1] >> 4);
@@ -87,7 +121,7 @@ macro_rules! impl_lcm_array {
     }
 }
 
-impl_lcm_array!([32 4 1]);
+impl_lcm_array!([32 16 8 4 1]);
 
 trait SplitFrom<T>: Sized {
     fn split_from(from: &mut T) -> &mut [Self];
@@ -125,100 +159,565 @@ impl<'a, T: 'a> TakePrefix for &'a [T] {
     }
 }
 
+/// A destination for encoded output.
+trait Output {
+    fn extend_from_slice(&mut self, data: &[u8]);
+}
+
+impl Output for Vec<u8> {
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        Vec::extend_from_slice(self, data)
+    }
+}
+
+struct ExtendOutput<'a, T: ?Sized>(&'a mut T);
+
+impl<T: Extend<u8> + ?Sized> Output for ExtendOutput<'_, T> {
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        self.0.extend(data.iter().cloned())
+    }
+}
+
+/// Like `std::io::Write for &mut [u8]`, writing advances the slice past the
+/// written data. The caller must make sure the slice is large enough.
+impl Output for &mut [u8] {
+    fn extend_from_slice(&mut self, data: &[u8]) {
+        let (head, tail) = core::mem::take(self).split_at_mut(data.len());
+        head.copy_from_slice(data);
+        *self = tail;
+    }
+}
+
 impl crate::Newline {
-    fn append_to(self, buf: &mut Vec<u8>) {
-        if let crate::Newline::CRLF = self {
-            buf.push(b'\r');
+    fn len(self) -> usize {
+        match self {
+            crate::Newline::LF => 1,
+            crate::Newline::CRLF => 2,
         }
-        buf.push(b'\n');
+    }
+
+    fn append_to<O: Output>(self, out: &mut O) {
+        out.extend_from_slice(&b"\r\n"[(2 - self.len())..]);
     }
 }
 
-fn encode64<E: Encoder, U: Unpacker, L>(
-    input: &[u8],
+/// Writes output while inserting newlines according to
+/// `Config::line_length`.
+struct LineWrapper {
     config: crate::Config,
+    column: usize,
+}
+
+/// Panics if `config` would wrap lines without any characters in them.
+fn check_line_length(config: crate::Config) {
+    assert_ne!(
+        config.line_length,
+        Some(0),
+        "`Config::line_length` must not be `Some(0)`"
+    );
+}
+
+impl LineWrapper {
+    fn new(config: crate::Config) -> LineWrapper {
+        check_line_length(config);
+        LineWrapper { config, column: 0 }
+    }
+
+    /// The number of bytes `write` will output for `data_len` bytes of data.
+    fn output_len(&self, data_len: usize) -> usize {
+        match self.config.line_length {
+            Some(line_length) if data_len > 0 => {
+                data_len + (self.column + data_len - 1) / line_length * self.config.newline.len()
+            }
+            _ => data_len,
+        }
+    }
+
+    fn write<O: Output>(&mut self, mut data: &[u8], out: &mut O) {
+        let line_length = match self.config.line_length {
+            Some(line_length) => line_length,
+            None => return out.extend_from_slice(data),
+        };
+
+        while !data.is_empty() {
+            // Newlines are only inserted between lines, never at the end of
+            // the output.
+            if self.column == line_length {
+                self.config.newline.append_to(out);
+                self.column = 0;
+            }
+            let line = data.take_prefix(cmp::min(line_length - self.column, data.len()));
+            out.extend_from_slice(line);
+            self.column += line.len();
+        }
+    }
+}
+
+/// The exact length of the output of encoding `input_len` bytes, or `None`
+/// if it overflows `usize`.
+fn checked_encoded_len(input_len: usize, config: crate::Config) -> Option<usize> {
+    check_line_length(config);
+    let partial = match (input_len % 3, config.pad) {
+        (0, _) => 0,
+        (_, true) => 4,
+        (rem, false) => rem + 1,
+    };
+    let mut len = (input_len / 3).checked_mul(4)?.checked_add(partial)?;
+    if let Some(line_length) = config.line_length {
+        if len > 0 {
+            len = len.checked_add(((len - 1) / line_length).checked_mul(config.newline.len())?)?;
+        }
+    }
+    Some(len)
+}
+
+/// The exact length of the output of encoding `input_len` bytes. Panics if it
+/// overflows `usize`.
+pub(super) fn encoded_len(input_len: usize, config: crate::Config) -> usize {
+    checked_encoded_len(input_len, config).expect("encoded length overflows `usize`")
+}
+
+/// Incremental encoding state.
+///
+/// Input is encoded in groups of `size_of::<L>() / 4 * 3` bytes, a partial
+/// group is kept in `pending` until more input arrives or the encoding is
+/// finished.
+struct EncodeState<E: Encoder, U: Unpacker, L: AsMut<[u8]>> {
     encoder: E,
     unpacker: U,
-) -> String
+    wrapper: LineWrapper,
+    pending: Scratch<L>,
+    pending_len: usize,
+}
+
+impl<E: Encoder, U: Unpacker, L> EncodeState<E, U, L>
 where
     L: Lcm<U::Output, E::Block>,
     U::Output: SplitFrom<L>,
     E::Block: SplitFrom<L>,
 {
-    let mut len = crate::misc::div_roundup(input.len(), 3) * 4;
-    let mut next_nl = config.line_length;
-    if let Some(line_length) = config.line_length {
-        let nl_len = match config.newline {
-            crate::Newline::LF => 1,
-            crate::Newline::CRLF => 2,
-        };
-        len = crate::misc::div_roundup(len, line_length) * (line_length + nl_len);
+    const GROUP_LEN: usize = size_of::<L>() / 4 * 3;
+
+    fn new(config: crate::Config, encoder: E, unpacker: U) -> Self {
+        EncodeState {
+            encoder,
+            unpacker,
+            wrapper: LineWrapper::new(config),
+            pending: Scratch(L::default()),
+            pending_len: 0,
+        }
     }
-    let mut output = Vec::with_capacity(len);
 
-    let mut buffer = L::default();
+    /// Encode at most `GROUP_LEN` bytes. Unless this is the last group,
+    /// `input` must be exactly `GROUP_LEN` bytes long.
+    fn encode_group<O: Output>(
+        encoder: E,
+        unpacker: U,
+        wrapper: &mut LineWrapper,
+        input: &[u8],
+        output: &mut O,
+    ) {
+        let mut buffer = Scratch(L::default());
 
-    let mut input_iter = input.chunks(size_of::<U::Input>());
-    while input_iter.len() > 0 {
-        let mut input_len = 0;
+        let mut input_iter = input.chunks(size_of::<U::Input>());
         for chunk in <U::Output>::split_from(&mut buffer) {
-            let mut input_block = U::Input::default();
+            let mut input_block = Scratch(U::Input::default());
             if let Some(input_next) = input_iter.next() {
-                input_len += input_next.len();
                 input_block.as_mut()[..input_next.len()].copy_from_slice(input_next);
             }
             unpacker.unpack_block(&input_block, chunk);
         }
         for chunk in <E::Block>::split_from(&mut buffer) {
-            encoder.encode_block(chunk, config.char_set);
+            encoder.encode_block(chunk, wrapper.config.char_set);
         }
 
-        let mut buffer = &buffer.as_ref()[..crate::misc::div_roundup(input_len * 4, 3)];
+        wrapper.write(
+            &buffer.as_ref()[..crate::misc::div_roundup(input.len() * 4, 3)],
+            output,
+        );
+    }
+
+    /// The number of bytes `update` will output for `input_len` bytes of
+    /// input.
+    fn update_len(&self, input_len: usize) -> usize {
+        let groups = (self.pending_len + input_len) / Self::GROUP_LEN;
+        self.wrapper.output_len(groups * Self::GROUP_LEN / 3 * 4)
+    }
+
+    /// The number of bytes `finish` will output.
+    fn finish_len(&self) -> usize {
+        let len = if self.wrapper.config.pad {
+            crate::misc::div_roundup(self.pending_len, 3) * 4
+        } else {
+            crate::misc::div_roundup(self.pending_len * 4, 3)
+        };
+        self.wrapper.output_len(len)
+    }
 
-        if let Some(mut nl_index) = next_nl {
-            while (output.len() + buffer.len()) > nl_index {
-                let line = buffer.take_prefix(nl_index - output.len());
-                output.extend_from_slice(&line);
-                config.newline.append_to(&mut output);
-                nl_index = output.len() + config.line_length.unwrap();
+    fn update<O: Output>(&mut self, mut input: &[u8], output: &mut O) {
+        if self.pending_len > 0 {
+            let fill = cmp::min(Self::GROUP_LEN - self.pending_len, input.len());
+            self.pending.as_mut()[self.pending_len..][..fill]
+                .copy_from_slice(input.take_prefix(fill));
+            self.pending_len += fill;
+            if self.pending_len < Self::GROUP_LEN {
+                return;
             }
-            next_nl = Some(nl_index);
+            Self::encode_group(
+                self.encoder,
+                self.unpacker,
+                &mut self.wrapper,
+                &self.pending.as_ref()[..Self::GROUP_LEN],
+                output,
+            );
+            self.pending_len = 0;
         }
 
-        output.extend_from_slice(buffer);
+        while input.len() >= Self::GROUP_LEN {
+            let group = input.take_prefix(Self::GROUP_LEN);
+            Self::encode_group(
+                self.encoder,
+                self.unpacker,
+                &mut self.wrapper,
+                group,
+                output,
+            );
+        }
+
+        self.pending.as_mut()[..input.len()].copy_from_slice(input);
+        self.pending_len = input.len();
     }
 
-    if config.pad {
-        if let Some(mut nl_index) = next_nl {
-            let trailer_length = match input.len() % 3 {
+    /// Encode any pending input and write the trailer. The state is reset
+    /// for encoding a new input.
+    fn finish<O: Output>(&mut self, output: &mut O) {
+        if self.pending_len > 0 {
+            Self::encode_group(
+                self.encoder,
+                self.unpacker,
+                &mut self.wrapper,
+                &self.pending.as_ref()[..self.pending_len],
+                output,
+            );
+        }
+
+        // Groups are a multiple of 3 bytes long, so only the pending input
+        // determines the trailer length.
+        if self.wrapper.config.pad {
+            let trailer_length = match self.pending_len % 3 {
                 1 => 2,
                 2 => 1,
                 _ => 0,
             };
-            for _ in 0..trailer_length {
-                if output.len() == nl_index {
-                    config.newline.append_to(&mut output);
-                    nl_index = output.len() + config.line_length.unwrap();
-                }
-                output.push(b'=');
-            }
-        } else if output.len() != len {
-            output.resize(len, b'=');
+            self.wrapper.write(&b"=="[..trailer_length], output);
         }
+
+        wipe(self.pending.as_mut());
+        self.pending_len = 0;
+        self.wrapper.column = 0;
     }
+}
 
+fn encode64_to<E: Encoder, U: Unpacker, L, O: Output>(
+    input: &[u8],
+    config: crate::Config,
+    encoder: E,
+    unpacker: U,
+    output: &mut O,
+) where
+    L: Lcm<U::Output, E::Block>,
+    U::Output: SplitFrom<L>,
+    E::Block: SplitFrom<L>,
+{
+    let mut state = EncodeState::<E, U, L>::new(config, encoder, unpacker);
+    state.update(input, output);
+    state.finish(output);
+}
+
+fn encode64<E: Encoder, U: Unpacker, L>(
+    input: &[u8],
+    config: crate::Config,
+    encoder: E,
+    unpacker: U,
+) -> String
+where
+    L: Lcm<U::Output, E::Block>,
+    U::Output: SplitFrom<L>,
+    E::Block: SplitFrom<L>,
+{
+    let mut output = Vec::with_capacity(encoded_len(input.len(), config));
+    encode64_to(input, config, encoder, unpacker, &mut output);
     String::from_utf8(output).unwrap()
 }
 
-pub(super) fn encode64_arch(input: &[u8], config: crate::Config) -> String {
+/// Like `encode64`, but returns `Error::AllocationFailed` instead of aborting
+/// if the output can't be allocated.
+fn try_encode64<E: Encoder, U: Unpacker, L>(
+    input: &[u8],
+    config: crate::Config,
+    encoder: E,
+    unpacker: U,
+) -> Result<String, Error>
+where
+    L: Lcm<U::Output, E::Block>,
+    U::Output: SplitFrom<L>,
+    E::Block: SplitFrom<L>,
+{
+    // The exact output length is reserved, so encoding doesn't allocate.
+    let mut output = Vec::new();
+    output
+        .try_reserve_exact(checked_encoded_len(input.len(), config).ok_or(Error::AllocationFailed)?)
+        .map_err(|_| Error::AllocationFailed)?;
+    encode64_to(input, config, encoder, unpacker, &mut output);
+    Ok(String::from_utf8(output).unwrap())
+}
+
+fn encode64_to_slice<E: Encoder, U: Unpacker, L>(
+    input: &[u8],
+    config: crate::Config,
+    encoder: E,
+    unpacker: U,
+    output: &mut [u8],
+) -> Result<usize, Error>
+where
+    L: Lcm<U::Output, E::Block>,
+    U::Output: SplitFrom<L>,
+    E::Block: SplitFrom<L>,
+{
+    let len = encoded_len(input.len(), config);
+    let mut output = output.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+    encode64_to(input, config, encoder, unpacker, &mut output);
+    debug_assert!(output.is_empty());
+    Ok(len)
+}
+
+/// The configuration of `encode64_to_array`: a single line, padded if `pad`.
+fn array_config(char_set: crate::CharacterSet, pad: bool) -> crate::Config {
+    crate::Config {
+        char_set,
+        newline: crate::Newline::LF,
+        pad,
+        line_length: None,
+    }
+}
+
+/// Encode `input` to exactly `M` characters on a single line, with padding if
+/// `PAD`. `M` not being the encoded length of `N` bytes is a compile-time
+/// error.
+fn encode64_to_array<E: Encoder, U: Unpacker, L, const N: usize, const M: usize, const PAD: bool>(
+    input: &[u8; N],
+    char_set: crate::CharacterSet,
+    encoder: E,
+    unpacker: U,
+) -> [u8; M]
+where
+    L: Lcm<U::Output, E::Block>,
+    U::Output: SplitFrom<L>,
+    E::Block: SplitFrom<L>,
+{
+    const {
+        let len = if PAD {
+            N.div_ceil(3) * 4
+        } else {
+            (N * 4).div_ceil(3)
+        };
+        assert!(M == len, "M must be the encoded length of N bytes");
+    }
+    let config = array_config(char_set, PAD);
+
+    let mut output = [0u8; M];
+    let mut out = &mut output[..];
+    encode64_to(input, config, encoder, unpacker, &mut out);
+    debug_assert!(out.is_empty());
+    output
+}
+
+/// An incremental encoder using the implementation selected by
+/// [`crate::backend()`].
+enum ArchEncoder {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
-    unsafe {
-        if is_x86_feature_detected!("avx2") {
-            let avx2 = avx2::Avx2::new();
-            return encode64(input, config, avx2, avx2);
+    Avx2(EncodeState<avx2::Avx2, avx2::Avx2, [u8; 32]>),
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    Ssse3(EncodeState<ssse3::Ssse3, ssse3::Ssse3, [u8; 16]>),
+    Swar(EncodeState<swar::Swar, swar::Swar, [u8; 8]>),
+    Arithmetic(EncodeState<arithmetic::Arithmetic, Simple, [u8; 4]>),
+    LutAlign64(EncodeState<lut_align64::LutAlign64, Simple, [u8; 4]>),
+}
+
+/// Call a method on the `EncodeState` inside an `ArchEncoder`.
+macro_rules! arch_encoder_dispatch {
+    ($self:expr, $state:ident => $e:expr) => {
+        match $self {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ArchEncoder::Avx2($state) => $e,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            ArchEncoder::Ssse3($state) => $e,
+            ArchEncoder::Swar($state) => $e,
+            ArchEncoder::Arithmetic($state) => $e,
+            ArchEncoder::LutAlign64($state) => $e,
+        }
+    };
+}
+
+impl ArchEncoder {
+    fn new(config: crate::Config) -> ArchEncoder {
+        use crate::Backend;
+
+        // SAFETY: `backend()` only returns backends that are available on
+        // the runtime CPU.
+        match crate::backend() {
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Avx2 => unsafe {
+                let avx2 = avx2::Avx2::new();
+                ArchEncoder::Avx2(EncodeState::new(config, avx2, avx2))
+            },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Backend::Ssse3 => unsafe {
+                let ssse3 = ssse3::Ssse3::new();
+                ArchEncoder::Ssse3(EncodeState::new(config, ssse3, ssse3))
+            },
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            Backend::Avx2 | Backend::Ssse3 => unreachable!(),
+            Backend::Swar => ArchEncoder::Swar(EncodeState::new(config, swar::Swar, swar::Swar)),
+            Backend::Arithmetic => {
+                ArchEncoder::Arithmetic(EncodeState::new(config, arithmetic::Arithmetic, Simple))
+            }
+            Backend::LutAlign64 => {
+                ArchEncoder::LutAlign64(EncodeState::new(config, lut_align64::LutAlign64, Simple))
+            }
         }
     }
-    encode64(input, config, lut_align64::LutAlign64, Simple)
+
+    fn update_len(&self, input_len: usize) -> usize {
+        arch_encoder_dispatch!(self, state => state.update_len(input_len))
+    }
+
+    fn finish_len(&self) -> usize {
+        arch_encoder_dispatch!(self, state => state.finish_len())
+    }
+
+    fn update<O: Output>(&mut self, input: &[u8], output: &mut O) {
+        arch_encoder_dispatch!(self, state => state.update(input, output))
+    }
+
+    fn finish<O: Output>(&mut self, output: &mut O) {
+        arch_encoder_dispatch!(self, state => state.finish(output))
+    }
+}
+
+pub(super) fn encode64_arch(input: &[u8], config: crate::Config) -> String {
+    arch_encoder_dispatch!(ArchEncoder::new(config), state => {
+        encode64(input, config, state.encoder, state.unpacker)
+    })
+}
+
+pub(super) fn try_encode64_arch(input: &[u8], config: crate::Config) -> Result<String, Error> {
+    arch_encoder_dispatch!(ArchEncoder::new(config), state => {
+        try_encode64(input, config, state.encoder, state.unpacker)
+    })
+}
+
+pub(super) fn encode64_to_array_arch<const N: usize, const M: usize, const PAD: bool>(
+    input: &[u8; N],
+    char_set: crate::CharacterSet,
+) -> [u8; M] {
+    arch_encoder_dispatch!(ArchEncoder::new(array_config(char_set, PAD)), state => {
+        encode64_to_array::<_, _, _, N, M, PAD>(input, char_set, state.encoder, state.unpacker)
+    })
+}
+
+pub(super) fn encode64_to_slice_arch(
+    input: &[u8],
+    config: crate::Config,
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    arch_encoder_dispatch!(ArchEncoder::new(config), state => {
+        encode64_to_slice(input, config, state.encoder, state.unpacker, output)
+    })
+}
+
+/// An incremental base64 encoder.
+///
+/// This encodes input that is provided in chunks, without allocating. The
+/// output is identical to that of [`ToBase64::to_base64`] on the
+/// concatenation of all chunks, including line wrapping and padding.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::{Base64Encoder, STANDARD};
+///
+/// let mut encoder = Base64Encoder::new(STANDARD);
+/// let mut output = Vec::new();
+/// encoder.update(b"Hello, ", &mut output);
+/// encoder.update(b"World", &mut output);
+/// encoder.finalize(&mut output);
+/// assert_eq!(output, b"SGVsbG8sIFdvcmxk");
+/// ```
+///
+/// [`ToBase64::to_base64`]: crate::ToBase64::to_base64
+pub struct Base64Encoder(ArchEncoder);
+
+impl Base64Encoder {
+    /// Create a new encoder following the specified format configuration.
+    pub fn new(config: crate::Config) -> Base64Encoder {
+        Base64Encoder(ArchEncoder::new(config))
+    }
+
+    /// Encode the next chunk of input, appending the output to `output`.
+    ///
+    /// Input that doesn't fill a complete block is buffered until the next
+    /// call to `update` or `finalize`.
+    pub fn update<T: Extend<u8> + ?Sized>(&mut self, input: &[u8], output: &mut T) {
+        self.0.update(input, &mut ExtendOutput(output))
+    }
+
+    /// Encode the next chunk of input, writing the output to the beginning of
+    /// `output`. Returns the number of bytes written.
+    ///
+    /// Returns `ToBase64Error::BufferTooSmall` if `output` is shorter than
+    /// [`update_len`]`(input.len())`, in which case no input is consumed.
+    ///
+    /// [`update_len`]: Base64Encoder::update_len
+    pub fn update_to_slice(&mut self, input: &[u8], output: &mut [u8]) -> Result<usize, Error> {
+        let len = self.0.update_len(input.len());
+        let mut output = output.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        self.0.update(input, &mut output);
+        Ok(len)
+    }
+
+    /// Encode any buffered input and append the trailer to `output`.
+    ///
+    /// The encoder is reset and can be used to encode a new input.
+    pub fn finalize<T: Extend<u8> + ?Sized>(&mut self, output: &mut T) {
+        self.0.finish(&mut ExtendOutput(output))
+    }
+
+    /// Encode any buffered input and write the trailer to the beginning of
+    /// `output`. Returns the number of bytes written.
+    ///
+    /// Returns `ToBase64Error::BufferTooSmall` if `output` is shorter than
+    /// [`finalize_len`]`()`, in which case the encoder is unchanged.
+    /// Otherwise, the encoder is reset and can be used to encode a new input.
+    ///
+    /// [`finalize_len`]: Base64Encoder::finalize_len
+    pub fn finalize_to_slice(&mut self, output: &mut [u8]) -> Result<usize, Error> {
+        let len = self.0.finish_len();
+        let mut output = output.get_mut(..len).ok_or(Error::BufferTooSmall)?;
+        self.0.finish(&mut output);
+        Ok(len)
+    }
+
+    /// The number of bytes `update` will output for `input_len` bytes of
+    /// input.
+    pub fn update_len(&self, input_len: usize) -> usize {
+        self.0.update_len(input_len)
+    }
+
+    /// The number of bytes `finalize` will output.
+    pub fn finalize_len(&self) -> usize {
+        self.0.finish_len()
+    }
 }
 
 #[cfg(test)]
@@ -232,60 +731,75 @@ mod tests {
         unsafe { avx2::Avx2::new() }
     }
 
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    pub(super) fn test_ssse3() -> ssse3::Ssse3 {
+        unsafe { ssse3::Ssse3::new() }
+    }
+
     generate_tests![
         encoders<E>: {
             lut_align64, lut_align64::LutAlign64;
+            arithmetic, arithmetic::Arithmetic;
+            swar, swar::Swar;
             #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] avx2, test_avx2();
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] ssse3, test_ssse3();
         },
         unpackers<U>: {
             simple, Simple;
             #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] avx2, test_avx2();
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))] ssse3, test_ssse3();
+            swar, swar::Swar;
         },
         tests: {
             encode,
+            encode_to_slice,
+            encode_to_array,
+            encode_incremental,
+            encode_incremental_len,
+            all_values,
         },
     ];
 
+    static ENCODE_TESTS: &[(&[u8], Config, &str)] = &[
+        // basic tests (from rustc-serialize)
+        (b"", STANDARD, ""),
+        (b"f", STANDARD, "Zg=="),
+        (b"fo", STANDARD, "Zm8="),
+        (b"foo", STANDARD, "Zm9v"),
+        (b"foob", STANDARD, "Zm9vYg=="),
+        (b"fooba", STANDARD, "Zm9vYmE="),
+        (b"foobar", STANDARD, "Zm9vYmFy"),
+        // with crlf break (from rustc-serialize)
+        (b"foobar", Config {line_length: Some(4), ..STANDARD}, "Zm9v\r\nYmFy"),
+        // with lf break (from rustc-serialize)
+        (b"foobar", Config {line_length: Some(4), newline: Newline::LF, ..STANDARD}, "Zm9v\nYmFy"),
+        // without padding (from rustc-serialize)
+        (b"f", Config {pad: false, ..STANDARD}, "Zg"),
+        (b"fo", Config {pad: false, ..STANDARD}, "Zm8"),
+        // URL safe (from rustc-serialize)
+        (&[251, 255], URL_SAFE, "-_8"),
+        (&[251, 255], STANDARD, "+/8="),
+
+        // new tests
+        (b"f", Config {line_length: Some(1), ..STANDARD}, "Z\r\ng\r\n=\r\n="),
+        (b"fo", Config {line_length: Some(1), ..STANDARD}, "Z\r\nm\r\n8\r\n="),
+        (b"foob", Config {line_length: Some(4), ..STANDARD}, "Zm9v\r\nYg=="),
+        (b"foob", Config {line_length: Some(5), ..STANDARD}, "Zm9vY\r\ng=="),
+        (b"foob", Config {line_length: Some(6), ..STANDARD}, "Zm9vYg\r\n=="),
+        (b"foob", Config {line_length: Some(7), ..STANDARD}, "Zm9vYg=\r\n="),
+        (b"foob", Config {line_length: Some(8), ..STANDARD}, "Zm9vYg=="),
+        (b"foobfoo", Config {line_length: Some(3), ..STANDARD}, "Zm9\r\nvYm\r\nZvb\r\nw=="),
+        (b"foobfoo", Config {line_length: Some(4), ..STANDARD}, "Zm9v\r\nYmZv\r\nbw=="),
+        (b"foobfoo", Config {line_length: Some(5), ..STANDARD}, "Zm9vY\r\nmZvbw\r\n=="),
+        (b"\x00\x10\x83\x10\x51\x87\x20\x92\x8b\x30\xd3\x8f\x41\x14\x93\x51\x55\x97\x61\x96\x9b\x71\xd7\x9f", STANDARD, "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdef"),
+    ];
+
     fn encode<E: Encoder, U: Unpacker, L>(encoder: E, unpacker: U)
     where
         L: Lcm<U::Output, E::Block>,
         U::Output: SplitFrom<L>,
         E::Block: SplitFrom<L>,
     {
-        static ENCODE_TESTS: &[(&[u8], Config, &str)] = &[
-            // basic tests (from rustc-serialize)
-            (b"", STANDARD, ""),
-            (b"f", STANDARD, "Zg=="),
-            (b"fo", STANDARD, "Zm8="),
-            (b"foo", STANDARD, "Zm9v"),
-            (b"foob", STANDARD, "Zm9vYg=="),
-            (b"fooba", STANDARD, "Zm9vYmE="),
-            (b"foobar", STANDARD, "Zm9vYmFy"),
-            // with crlf break (from rustc-serialize)
-            (b"foobar", Config {line_length: Some(4), ..STANDARD}, "Zm9v\r\nYmFy"),
-            // with lf break (from rustc-serialize)
-            (b"foobar", Config {line_length: Some(4), newline: Newline::LF, ..STANDARD}, "Zm9v\nYmFy"),
-            // without padding (from rustc-serialize)
-            (b"f", Config {pad: false, ..STANDARD}, "Zg"),
-            (b"fo", Config {pad: false, ..STANDARD}, "Zm8"),
-            // URL safe (from rustc-serialize)
-            (&[251, 255], URL_SAFE, "-_8"),
-            (&[251, 255], STANDARD, "+/8="),
-
-            // new tests
-            (b"f", Config {line_length: Some(1), ..STANDARD}, "Z\r\ng\r\n=\r\n="),
-            (b"fo", Config {line_length: Some(1), ..STANDARD}, "Z\r\nm\r\n8\r\n="),
-            (b"foob", Config {line_length: Some(4), ..STANDARD}, "Zm9v\r\nYg=="),
-            (b"foob", Config {line_length: Some(5), ..STANDARD}, "Zm9vY\r\ng=="),
-            (b"foob", Config {line_length: Some(6), ..STANDARD}, "Zm9vYg\r\n=="),
-            (b"foob", Config {line_length: Some(7), ..STANDARD}, "Zm9vYg=\r\n="),
-            (b"foob", Config {line_length: Some(8), ..STANDARD}, "Zm9vYg=="),
-            (b"foobfoo", Config {line_length: Some(3), ..STANDARD}, "Zm9\r\nvYm\r\nZvb\r\nw=="),
-            (b"foobfoo", Config {line_length: Some(4), ..STANDARD}, "Zm9v\r\nYmZv\r\nbw=="),
-            (b"foobfoo", Config {line_length: Some(5), ..STANDARD}, "Zm9vY\r\nmZvbw\r\n=="),
-            (b"\x00\x10\x83\x10\x51\x87\x20\x92\x8b\x30\xd3\x8f\x41\x14\x93\x51\x55\x97\x61\x96\x9b\x71\xd7\x9f", STANDARD, "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdef"),
-        ];
-
         for (input, config, expected) in ENCODE_TESTS {
             let output = encode64(input, *config, encoder, unpacker);
             if &output != expected {
@@ -296,14 +810,203 @@ mod tests {
                     expected
                 );
             }
+            assert_eq!(try_encode64(input, *config, encoder, unpacker), Ok(output));
+        }
+    }
+
+    fn encode_to_array<E: Encoder, U: Unpacker, L>(encoder: E, unpacker: U)
+    where
+        L: Lcm<U::Output, E::Block>,
+        U::Output: SplitFrom<L>,
+        E::Block: SplitFrom<L>,
+    {
+        let output: [u8; 8] =
+            encode64_to_array::<_, _, _, 5, 8, true>(b"fooba", crate::Standard, encoder, unpacker);
+        assert_eq!(&output, b"Zm9vYmE=");
+        let output: [u8; 7] =
+            encode64_to_array::<_, _, _, 5, 7, false>(b"fooba", crate::Standard, encoder, unpacker);
+        assert_eq!(&output, b"Zm9vYmE");
+        let output: [u8; 0] =
+            encode64_to_array::<_, _, _, 0, 0, true>(b"", crate::Standard, encoder, unpacker);
+        assert_eq!(&output, b"");
+        let output: [u8; 0] =
+            encode64_to_array::<_, _, _, 0, 0, false>(b"", crate::Standard, encoder, unpacker);
+        assert_eq!(&output, b"");
+
+        use rand::RngCore;
+
+        let mut input = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut input);
+        for config in [STANDARD, URL_SAFE] {
+            let padded: [u8; 44] = encode64_to_array::<_, _, _, 32, 44, true>(
+                &input,
+                config.char_set,
+                encoder,
+                unpacker,
+            );
+            let unpadded: [u8; 43] = encode64_to_array::<_, _, _, 32, 43, false>(
+                &input,
+                config.char_set,
+                encoder,
+                unpacker,
+            );
+            let expected = encode64(&input, config, encoder, unpacker);
+            assert_eq!(&padded[..43], expected.trim_end_matches('=').as_bytes());
+            assert_eq!(&padded[43..], b"=");
+            assert_eq!(&unpadded[..], &padded[..43]);
+        }
+    }
+
+    fn encode_to_slice<E: Encoder, U: Unpacker, L>(encoder: E, unpacker: U)
+    where
+        L: Lcm<U::Output, E::Block>,
+        U::Output: SplitFrom<L>,
+        E::Block: SplitFrom<L>,
+    {
+        for (input, config, expected) in ENCODE_TESTS {
+            assert_eq!(encoded_len(input.len(), *config), expected.len());
+
+            let mut output = [0u8; 64];
+            let len = encode64_to_slice(input, *config, encoder, unpacker, &mut output).unwrap();
+            assert_eq!(&output[..len], expected.as_bytes());
+
+            if !expected.is_empty() {
+                let mut output = std::vec![0u8; expected.len() - 1];
+                assert!(matches!(
+                    encode64_to_slice(input, *config, encoder, unpacker, &mut output),
+                    Err(Error::BufferTooSmall)
+                ));
+            }
+        }
+    }
+
+    fn encode_incremental<E: Encoder, U: Unpacker, L>(encoder: E, unpacker: U)
+    where
+        L: Lcm<U::Output, E::Block>,
+        U::Output: SplitFrom<L>,
+        E::Block: SplitFrom<L>,
+    {
+        use rand::{thread_rng, Rng, RngCore};
+
+        let mut rng = thread_rng();
+        for len in 0..200 {
+            let mut input = std::vec![0; len];
+            rng.fill_bytes(&mut input);
+            let config = Config {
+                line_length: Some(7),
+                ..STANDARD
+            };
+
+            let mut state = EncodeState::<E, U, L>::new(config, encoder, unpacker);
+            let mut output = Vec::new();
+            let mut rest = &input[..];
+            while !rest.is_empty() {
+                let chunk = rest.take_prefix(rng.gen_range(1, rest.len() + 1));
+                state.update(chunk, &mut output);
+            }
+            state.finish(&mut output);
+
+            assert_eq!(
+                std::str::from_utf8(&output).unwrap(),
+                encode64(&input, config, encoder, unpacker)
+            );
         }
     }
+
+    fn encode_incremental_len<E: Encoder, U: Unpacker, L>(encoder: E, unpacker: U)
+    where
+        L: Lcm<U::Output, E::Block>,
+        U::Output: SplitFrom<L>,
+        E::Block: SplitFrom<L>,
+    {
+        use rand::{thread_rng, Rng};
+
+        let mut rng = thread_rng();
+        for (_, config, _) in ENCODE_TESTS {
+            for config in &[
+                *config,
+                Config {
+                    pad: false,
+                    ..*config
+                },
+            ] {
+                let mut state = EncodeState::<E, U, L>::new(*config, encoder, unpacker);
+                let mut output = Vec::new();
+                for _ in 0..50 {
+                    let input_len = rng.gen_range(0, 40);
+                    let expected = state.update_len(input_len);
+                    let start = output.len();
+                    state.update(&std::vec![0; input_len], &mut output);
+                    assert_eq!(output.len() - start, expected);
+                }
+                let expected = state.finish_len();
+                let start = output.len();
+                state.finish(&mut output);
+                assert_eq!(output.len() - start, expected);
+            }
+        }
+    }
+
+    fn all_values<E: Encoder, U: Unpacker, L>(encoder: E, unpacker: U)
+    where
+        L: Lcm<U::Output, E::Block>,
+        U::Output: SplitFrom<L>,
+        E::Block: SplitFrom<L>,
+    {
+        // The 6-bit values 0 through 63, packed
+        let input = (0..16u32)
+            .flat_map(|i| {
+                let v = ((4 * i) << 18) | ((4 * i + 1) << 12) | ((4 * i + 2) << 6) | (4 * i + 3);
+                std::vec![(v >> 16) as u8, (v >> 8) as u8, v as u8]
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            encode64(&input, STANDARD, encoder, unpacker),
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+        );
+        assert_eq!(
+            encode64(&input, URL_SAFE, encoder, unpacker),
+            "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+        );
+    }
+
+    #[test]
+    fn encoded_len_overflow() {
+        let max_input = usize::MAX / 4 * 3;
+        assert_eq!(
+            checked_encoded_len(max_input, STANDARD),
+            Some(usize::MAX / 4 * 4)
+        );
+        assert_eq!(checked_encoded_len(max_input + 1, STANDARD), None);
+        assert_eq!(checked_encoded_len(max_input, crate::MIME), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "`Config::line_length` must not be `Some(0)`")]
+    fn zero_line_length() {
+        encode64_arch(
+            b"foo",
+            Config {
+                line_length: Some(0),
+                ..STANDARD
+            },
+        );
+    }
+
+    #[test]
+    fn display_errors() {
+        println!("Buffer too small is {}", Error::BufferTooSmall);
+        println!("Allocation failed is {}", Error::AllocationFailed);
+    }
 }
 
 #[cfg(all(test, feature = "nightly"))]
 mod benches {
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     use super::tests::test_avx2;
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    use super::tests::test_ssse3;
     use super::*;
 
     use test::Bencher;
@@ -321,6 +1024,17 @@ mod benches {
         });
     }
 
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[bench]
+    fn ssse3_1mb(b: &mut Bencher) {
+        let mut input = std::vec![0; 1024*1024];
+        thread_rng().fill_bytes(&mut input);
+        b.iter(|| {
+            let ret = encode64(&input, crate::STANDARD, test_ssse3(), test_ssse3());
+            std::hint::black_box(ret);
+        });
+    }
+
     #[bench]
     fn lut_align64_1mb(b: &mut Bencher) {
         let mut input = std::vec![0; 1024*1024];
@@ -330,6 +1044,24 @@ mod benches {
             std::hint::black_box(ret);
         });
     }
+    #[bench]
+    fn arithmetic_1mb(b: &mut Bencher) {
+        let mut input = std::vec![0; 1024*1024];
+        thread_rng().fill_bytes(&mut input);
+        b.iter(|| {
+            let ret = encode64(&input, crate::STANDARD, arithmetic::Arithmetic, Simple);
+            std::hint::black_box(ret);
+        });
+    }
+    #[bench]
+    fn swar_1mb(b: &mut Bencher) {
+        let mut input = std::vec![0; 1024*1024];
+        thread_rng().fill_bytes(&mut input);
+        b.iter(|| {
+            let ret = encode64(&input, crate::STANDARD, swar::Swar, swar::Swar);
+            std::hint::black_box(ret);
+        });
+    }
 
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     #[bench]
@@ -342,6 +1074,17 @@ mod benches {
         });
     }
 
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    #[bench]
+    fn ssse3_1kb(b: &mut Bencher) {
+        let mut input = std::vec![0; 1024];
+        thread_rng().fill_bytes(&mut input);
+        b.iter(|| {
+            let ret = encode64(&input, crate::STANDARD, test_ssse3(), test_ssse3());
+            std::hint::black_box(ret);
+        });
+    }
+
     #[bench]
     fn lut_align64_1kb(b: &mut Bencher) {
         let mut input = std::vec![0; 1024];
@@ -351,4 +1094,22 @@ mod benches {
             std::hint::black_box(ret);
         });
     }
+    #[bench]
+    fn arithmetic_1kb(b: &mut Bencher) {
+        let mut input = std::vec![0; 1024];
+        thread_rng().fill_bytes(&mut input);
+        b.iter(|| {
+            let ret = encode64(&input, crate::STANDARD, arithmetic::Arithmetic, Simple);
+            std::hint::black_box(ret);
+        });
+    }
+    #[bench]
+    fn swar_1kb(b: &mut Bencher) {
+        let mut input = std::vec![0; 1024];
+        thread_rng().fill_bytes(&mut input);
+        b.iter(|| {
+            let ret = encode64(&input, crate::STANDARD, swar::Swar, swar::Swar);
+            std::hint::black_box(ret);
+        });
+    }
 }
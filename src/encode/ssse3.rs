@@ -0,0 +1,108 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+use crate::ssse3::*;
+
+/// # Safety
+/// The caller should ensure the requisite CPU features are enabled.
+#[target_feature(enable = "ssse3,sse4.1")]
+unsafe fn encode_block(block: &mut <Ssse3 as super::Encoder>::Block, charset: crate::CharacterSet) {
+    // This is the same algorithm as in the AVX2 implementation, see there for
+    // details.
+    let input = array_as_m128i(*block);
+
+    // 0: lowercase letter, 1...10: number, 11: special character 1,
+    // 12: special character 2, 13: uppercase letter
+    let result = _mm_subs_epu8(input, _mm_set1_epi8(51));
+    let less = _mm_cmpgt_epi8(_mm_set1_epi8(26), input);
+    let result = _mm_or_si128(result, _mm_and_si128(less, _mm_set1_epi8(13)));
+
+    let (c62, c63) = match charset {
+        crate::CharacterSet::Standard => (b'+', b'/'),
+        crate::CharacterSet::UrlSafe => (b'-', b'_'),
+    };
+    let digit = b'0'.wrapping_sub(52);
+    #[rustfmt::skip]
+    let shift_lut = mm_setr_epu8([
+        b'a' - 26,
+        digit, digit, digit, digit, digit, digit, digit, digit, digit, digit,
+        c62.wrapping_sub(62),
+        c63.wrapping_sub(63),
+        b'A',
+        0, 0,
+    ]);
+
+    let shift = _mm_shuffle_epi8(shift_lut, result);
+
+    *block = m128i_as_array(_mm_add_epi8(shift, input));
+}
+
+/// # Safety
+/// The caller should ensure the requisite CPU features are enabled.
+#[target_feature(enable = "ssse3,sse4.1")]
+unsafe fn unpack_block(
+    input: &<Ssse3 as super::Unpacker>::Input,
+    output: &mut <Ssse3 as super::Unpacker>::Output,
+) {
+    let mut padded = [0u8; 16];
+    padded[..12].copy_from_slice(input);
+    let input = array_as_m128i(padded);
+    crate::misc::wipe(&mut padded);
+
+    #[rustfmt::skip]
+    let shuf = _mm_set_epi8(
+        10, 11, 9, 10,
+         7,  8, 6,  7,
+         4,  5, 3,  4,
+         1,  2, 0,  1,
+    );
+
+    let input = _mm_shuffle_epi8(input, shuf);
+
+    let t0 = _mm_and_si128(input, _mm_set1_epi32(0x0fc0fc00));
+    let t1 = _mm_mulhi_epu16(t0, _mm_set1_epi32(0x04000040));
+    let t2 = _mm_and_si128(input, _mm_set1_epi32(0x003f03f0));
+    let t3 = _mm_mullo_epi16(t2, _mm_set1_epi32(0x01000010));
+    *output = m128i_as_array(_mm_or_si128(t1, t3));
+}
+
+#[derive(Copy, Clone)]
+pub(super) struct Ssse3 {
+    _private: (),
+}
+
+impl Ssse3 {
+    /// # Safety
+    /// The caller should ensure the requisite CPU features are enabled.
+    #[target_feature(enable = "ssse3,sse4.1")]
+    pub(super) unsafe fn new() -> Ssse3 {
+        Ssse3 { _private: () }
+    }
+}
+
+impl super::Encoder for Ssse3 {
+    type Block = [u8; 16];
+
+    fn encode_block(self, block: &mut Self::Block, charset: crate::CharacterSet) {
+        // safe: `self` was given as a witness that the features are available
+        unsafe { encode_block(block, charset) }
+    }
+}
+
+impl super::Unpacker for Ssse3 {
+    type Input = [u8; 12];
+    type Output = [u8; 16];
+
+    fn unpack_block(self, input: &Self::Input, output: &mut Self::Output) {
+        // safe: `self` was given as a witness that the features are available
+        unsafe { unpack_block(input, output) }
+    }
+}
@@ -0,0 +1,50 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use crate::swar::{add, gt, splat};
+
+#[derive(Copy, Clone)]
+pub(super) struct Swar;
+
+impl super::Encoder for Swar {
+    type Block = [u8; 8];
+
+    fn encode_block(self, block: &mut Self::Block, charset: crate::CharacterSet) {
+        let (c62, c63) = match charset {
+            crate::Standard => (b'+', b'/'),
+            crate::UrlSafe => (b'-', b'_'),
+        };
+
+        // Same as `Arithmetic`, for 8 values at a time.
+        let v = u64::from_le_bytes(*block);
+        let mut c = add(v, splat(b'A'));
+        // 'a' through 'z'
+        c = add(c, gt(v, 25) & splat(b'a' - b'A' - 26));
+        // '0' through '9'
+        c = add(c, gt(v, 51) & splat(b'0'.wrapping_sub(b'a' + 26)));
+        // 62
+        c = add(c, gt(v, 61) & splat(c62.wrapping_sub(b'9' + 1)));
+        // 63
+        c = add(c, gt(v, 62) & splat(c63.wrapping_sub(c62 + 1)));
+        *block = c.to_le_bytes();
+    }
+}
+
+impl super::Unpacker for Swar {
+    type Input = [u8; 6];
+    type Output = [u8; 8];
+
+    fn unpack_block(self, input: &Self::Input, output: &mut Self::Output) {
+        // Two 24-bit big-endian groups in the two 32-bit lanes
+        let group = |i: &[u8]| (i[0] as u64) << 16 | (i[1] as u64) << 8 | i[2] as u64;
+        let x = group(&input[..3]) | group(&input[3..]) << 32;
+        // Split every 24-bit group into two 12-bit values in 16-bit lanes
+        let x = ((x >> 12) & 0x0000_0fff_0000_0fff) | ((x & 0x0000_0fff_0000_0fff) << 16);
+        // Split every 12-bit value into two 6-bit values in 8-bit lanes
+        let x = ((x >> 6) & 0x003f_003f_003f_003f) | ((x & 0x003f_003f_003f_003f) << 8);
+        *output = x.to_le_bytes();
+    }
+}
@@ -0,0 +1,260 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::io::{self, Write};
+use std::vec::Vec;
+
+use super::ArchEncoder;
+use crate::misc::{wipe, Scratch};
+
+/// The maximum number of input bytes encoded per call to `write`. This bounds
+/// the size of the internal output buffer.
+const MAX_INPUT_CHUNK: usize = 3 * 1024;
+
+/// A writer that base64-encodes all data written to it and writes the
+/// encoded output to an inner writer.
+///
+/// The output is identical to that of [`ToBase64::to_base64`] on the
+/// concatenation of all written data, including line wrapping and padding.
+/// Because the padding can only be written once all input is known, the
+/// encoding must be completed by calling [`finish`]. If the writer is
+/// dropped without calling `finish`, it is called implicitly and any errors
+/// are ignored.
+///
+/// Encoded output is buffered internally until it can be written to the
+/// inner writer. Errors writing to the inner writer are returned from the
+/// next call to `write`, `flush` or `finish`.
+///
+/// # Example
+///
+/// ```rust
+/// use std::io::Write;
+/// use b64_ct::{EncoderWriter, STANDARD};
+///
+/// let mut writer = EncoderWriter::new(Vec::new(), STANDARD);
+/// writer.write_all(b"Hello, ").unwrap();
+/// writer.write_all(b"World").unwrap();
+/// let output = writer.finish().unwrap();
+/// assert_eq!(output, b"SGVsbG8sIFdvcmxk");
+/// ```
+///
+/// [`ToBase64::to_base64`]: crate::ToBase64::to_base64
+/// [`finish`]: EncoderWriter::finish
+pub struct EncoderWriter<W: Write> {
+    inner: Option<W>,
+    encoder: ArchEncoder,
+    output: Scratch<Vec<u8>>,
+    output_pos: usize,
+    finished: bool,
+}
+
+impl<W: Write> EncoderWriter<W> {
+    /// Create a new encoder that writes the encoded output to `inner`,
+    /// following the specified format configuration.
+    pub fn new(inner: W, config: crate::Config) -> EncoderWriter<W> {
+        EncoderWriter {
+            inner: Some(inner),
+            encoder: ArchEncoder::new(config),
+            output: Scratch(Vec::new()),
+            output_pos: 0,
+            finished: false,
+        }
+    }
+
+    /// Encode any buffered input, write the trailer and return the inner
+    /// writer.
+    ///
+    /// If writing to the inner writer fails, `finish` may be called again to
+    /// retry writing the remaining output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `finish` has already returned successfully.
+    pub fn finish(&mut self) -> io::Result<W> {
+        if !self.finished {
+            // Write any output left by a failed write first, so that reserving
+            // can't move encoded data into a new allocation without wiping
+            // the old one.
+            self.write_output()?;
+            self.output.reserve(self.encoder.finish_len());
+            self.encoder.finish(&mut *self.output);
+            self.finished = true;
+        }
+        self.write_output()?;
+        self.inner.as_mut().expect(ALREADY_FINISHED).flush()?;
+        Ok(self.inner.take().unwrap())
+    }
+
+    /// Get a reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().expect(ALREADY_FINISHED)
+    }
+
+    /// Get a mutable reference to the inner writer.
+    ///
+    /// Writing directly to the inner writer may corrupt the encoded output.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.inner.as_mut().expect(ALREADY_FINISHED)
+    }
+
+    /// Write all buffered output to the inner writer.
+    fn write_output(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().expect(ALREADY_FINISHED);
+        while self.output_pos < self.output.len() {
+            match inner.write(&self.output[self.output_pos..]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write encoded output",
+                    ))
+                }
+                Ok(n) => self.output_pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        wipe(&mut self.output);
+        self.output.clear();
+        self.output_pos = 0;
+        Ok(())
+    }
+}
+
+const ALREADY_FINISHED: &str = "EncoderWriter used after finish()";
+
+impl<W: Write> Write for EncoderWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        assert!(!self.finished, "{}", ALREADY_FINISHED);
+        self.write_output()?;
+
+        let input = &buf[..buf.len().min(MAX_INPUT_CHUNK)];
+        debug_assert!(self.output.is_empty());
+        self.output.reserve(self.encoder.update_len(input.len()));
+        self.encoder.update(input, &mut *self.output);
+        // The input has been consumed, so errors can't be reported now. They
+        // will be reported by the next call that writes output.
+        let _ = self.write_output();
+        Ok(input.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_output()?;
+        self.inner.as_mut().expect(ALREADY_FINISHED).flush()
+    }
+}
+
+impl<W: Write> Drop for EncoderWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() && !std::thread::panicking() {
+            let _ = self.finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::{thread_rng, Rng, RngCore};
+
+    use crate::{Config, Newline, ToBase64, MIME, STANDARD, URL_SAFE};
+
+    static CONFIGS: &[Config] = &[
+        STANDARD,
+        URL_SAFE,
+        MIME,
+        Config {
+            line_length: Some(5),
+            newline: Newline::LF,
+            ..STANDARD
+        },
+        Config {
+            line_length: Some(64),
+            newline: Newline::LF,
+            ..URL_SAFE
+        },
+    ];
+
+    #[test]
+    fn same_as_to_base64() {
+        let mut rng = thread_rng();
+        for config in CONFIGS {
+            for len in (0..100).chain([1000, 4096, 10000].iter().cloned()) {
+                let mut input = std::vec![0; len];
+                rng.fill_bytes(&mut input);
+
+                let mut writer = EncoderWriter::new(Vec::new(), *config);
+                let mut rest = &input[..];
+                while !rest.is_empty() {
+                    let n = rng.gen_range(0, rest.len() + 1);
+                    writer.write_all(&rest[..n]).unwrap();
+                    rest = &rest[n..];
+                }
+                let output = writer.finish().unwrap();
+
+                assert_eq!(
+                    std::str::from_utf8(&output).unwrap(),
+                    input.to_base64(*config)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn finish_on_drop() {
+        let mut output = Vec::new();
+        {
+            let mut writer = EncoderWriter::new(&mut output, STANDARD);
+            writer.write_all(b"f").unwrap();
+        }
+        assert_eq!(output, b"Zg==");
+    }
+
+    /// A writer that accepts one byte per call and fails every other call.
+    struct Flaky {
+        output: Vec<u8>,
+        fail: bool,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.fail = !self.fail;
+            if self.fail {
+                return Err(io::Error::other("flaky"));
+            }
+            self.output.push(buf[0]);
+            Ok(1)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn retry_after_error() {
+        let input = b"Hello, World!";
+        let mut writer = EncoderWriter::new(
+            Flaky {
+                output: Vec::new(),
+                fail: false,
+            },
+            STANDARD,
+        );
+        let mut rest = &input[..];
+        while !rest.is_empty() {
+            if let Ok(n) = writer.write(rest) {
+                rest = &rest[n..];
+            }
+        }
+        let inner = loop {
+            if let Ok(inner) = writer.finish() {
+                break inner;
+            }
+        };
+        assert_eq!(inner.output, input.to_base64(STANDARD).as_bytes());
+    }
+}
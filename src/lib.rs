@@ -29,6 +29,40 @@
 //! dependent only on whitespace and the length of the valid data, not the data
 //! itself.
 //!
+//! When decoding, the padding trailer is detected and validated with the same
+//! control flow and memory access pattern for every block of input, so the
+//! position of the `=` characters relative to the data isn't revealed beyond
+//! what the length of the valid data already implies. Invalid input may be
+//! rejected in variable time.
+//!
+//! By default, the runtime of decoding also depends on the position of
+//! whitespace in the input. Setting [`DecodeConfig::oblivious`] removes this
+//! dependency from the control flow and from the number and length of the
+//! writes to the output, which then only depend on the length of the input.
+//! The position in the output buffer still advances with the number of
+//! decoded bytes, so the addresses written reveal how much of the input
+//! before each block was whitespace. Writes into a slice are clipped at its
+//! end, and output appended by [`Base64Decoder`] to an `Extend` target is
+//! written in pieces whose length depends on the position of whitespace.
+//!
+//! With the `zeroize` feature, all internal buffers that hold input or output
+//! data are wiped before they are released. When decoding fails, the output
+//! decoded so far is always wiped, whether or not the feature is enabled:
+//! both the output that would have been returned and what was written to a
+//! caller-provided slice. Output already appended to an `Extend` target by
+//! [`Base64Decoder`], and wiping buffers passed in by the caller in any other
+//! case, is left to the caller.
+//!
+//! To keep decoded key material from being copied or logged by accident, use
+//! [`FromBase64::from_base64_secret`] and [`ToBase64::to_base64_secret`],
+//! which return [`SecretBytes`] and [`SecretString`]. These are always wiped
+//! when dropped. With the `secrecy` feature, they can be converted into the
+//! corresponding types of the `secrecy` crate.
+//!
+//! The [`pem`] module parses and serializes PEM documents (RFC 7468), such
+//! as private keys and certificates, decoding the body with the same
+//! implementation.
+//!
 //! [BearSSL page on constant-time cryptography]: https://bearssl.org/constanttime.html
 //!
 //! # Implementation
@@ -38,12 +72,30 @@
 //!
 //! * x86 with AVX2: All lookup tables are implemented with SIMD
 //!   instructions. No secret-dependent memory accceses.
-//! * Other platforms: Lookups are limited to 64-byte aligned lookup tables. On
-//!   platforms with 64-byte cache lines this may be sufficient to prevent
-//!   certain cache side-channel attacks. However, it's known that this is [not
-//!   sufficient for all platforms].
+//! * x86 with SSSE3 and SSE4.1: Same as AVX2, using 128-bit SIMD
+//!   instructions. No secret-dependent memory accesses.
+//! * Other platforms: Characters are mapped using only branch-free arithmetic
+//!   and range checks, 8 at a time within a 64-bit integer. No lookup tables
+//!   and no secret-dependent memory accesses.
 //!
-//! [not sufficient on some platforms]: https://ts.data61.csiro.au/projects/TS/cachebleed/
+//! The implementation is detected once per process and can be inspected
+//! with [`backend()`]. It can be overridden for the whole process using
+//! [`set_backend`], or for the current thread using [`with_backend`], for
+//! example to compare implementations when auditing or reproducing an issue.
+//! Besides the implementations above, [`Backend::Arithmetic`] maps one
+//! character at a time using branch-free arithmetic, and
+//! [`Backend::LutAlign64`] uses lookup tables that each fit in a single
+//! 64-byte cache line. Streaming encoders and decoders keep the
+//! implementation that was selected when they were created.
+//!
+//! Without the `std` feature, CPU features are detected by querying CPUID
+//! directly. In SGX enclaves, where CPUID isn't available, only the CPU
+//! features enabled at compile time are used, so the SIMD implementations
+//! are only used in enclaves built with e.g.
+//! `-C target-feature=+avx2,+bmi1,+sse4.2,+popcnt` or
+//! `-C target-feature=+ssse3,+sse4.1`. The crate exports the
+//! `is_x86_feature_detected!` macro it uses for this when built without
+//! `std`.
 
 #![no_std]
 #![cfg_attr(all(test, feature = "nightly"), feature(test))]
@@ -63,15 +115,34 @@ mod test_support;
 #[macro_use]
 mod misc;
 
+#[cfg(all(
+    not(feature = "std"),
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(target_env = "sgx")
+))]
+#[doc(hidden)]
+pub use misc::cpuid::is_detected as __cpuid_is_detected;
+
+mod arithmetic;
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 mod avx2;
+mod backend;
 mod lut_align64;
+mod secret;
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod ssse3;
+mod swar;
 
 mod decode;
 mod encode;
+pub mod pem;
 
 use alloc::{string::String, vec::Vec};
 
+#[cfg(feature = "std")]
+pub use self::backend::with_backend;
+pub use self::backend::{backend, set_backend, Backend, BackendUnavailable};
+pub use self::secret::{SecretBytes, SecretString};
 pub use self::CharacterSet::*;
 
 /// Available encoding character sets
@@ -101,7 +172,8 @@ pub struct Config {
     pub newline: Newline,
     /// True to pad output with `=` characters
     pub pad: bool,
-    /// `Some(len)` to wrap lines at `len`, `None` to disable line wrapping
+    /// `Some(len)` to wrap lines at `len`, `None` to disable line wrapping.
+    /// Encoding panics if this is `Some(0)`.
     pub line_length: Option<usize>,
 }
 
@@ -129,11 +201,82 @@ pub static MIME: Config = Config {
     line_length: Some(76),
 };
 
+/// Available padding policies for decoding
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Padding {
+    /// Input must be padded with `=` characters to a multiple of 4
+    /// characters
+    Required,
+    /// Input must not contain `=` characters
+    Forbidden,
+    /// Input may be padded, but if it is, the padding must be correct
+    #[default]
+    Indifferent,
+}
+
+/// Available whitespace policies for decoding
+///
+/// ASCII whitespace consists of space, tab, line feed, vertical tab, form feed
+/// and carriage return, as in RFC 7468.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Whitespace {
+    /// Input must not contain whitespace
+    Forbidden,
+    /// Input may contain line feeds and carriage returns
+    LineBreaks,
+    /// Input may contain any ASCII whitespace
+    #[default]
+    Any,
+}
+
+/// Contains configuration parameters for [`decode_config`] and the other
+/// `_config` decoding functions.
+///
+/// The default configuration accepts everything `from_base64` accepts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DecodeConfig {
+    /// `Some(set)` to only accept the characters of `set`, `None` to accept
+    /// the characters of both character sets. This is an `Option` instead of
+    /// an additional `CharacterSet` variant, because `CharacterSet` is also
+    /// used by `Config`, where encoding with either set has no meaning.
+    pub char_set: Option<CharacterSet>,
+    /// True to reject input with non-zero bits in the last character that
+    /// are discarded when decoding, such that every byte string has exactly
+    /// one accepted encoding (apart from padding and whitespace)
+    pub canonical: bool,
+    /// Whether input must or must not be padded
+    pub padding: Padding,
+    /// Which whitespace characters are skipped. Other whitespace characters
+    /// are rejected as invalid characters.
+    pub whitespace: Whitespace,
+    /// True to make the control flow and the length of the writes to the
+    /// output independent of the position of whitespace in the input. This
+    /// is slower, and bytes past the end of the output in a caller-provided
+    /// slice may be overwritten with zeros. See the
+    /// [crate documentation](crate) for details.
+    pub oblivious: bool,
+}
+
 /// A trait for converting a value to base64 encoding.
 pub trait ToBase64 {
     /// Converts the value of `self` to a base64 value following the specified
     /// format configuration, returning the owned string.
     fn to_base64(&self, config: Config) -> String;
+
+    /// Like [`to_base64`](ToBase64::to_base64), but returns
+    /// `ToBase64Error::AllocationFailed` instead of aborting if the output
+    /// can't be allocated.
+    ///
+    /// The default implementation calls `to_base64`.
+    fn try_to_base64(&self, config: Config) -> Result<String, ToBase64Error> {
+        Ok(self.to_base64(config))
+    }
+
+    /// Like [`to_base64`](ToBase64::to_base64), but returns the encoded
+    /// value as a [`SecretString`] that is wiped from memory when dropped.
+    fn to_base64_secret(&self, config: Config) -> SecretString {
+        SecretString::new(self.to_base64(config))
+    }
 }
 
 impl ToBase64 for [u8] {
@@ -152,22 +295,193 @@ impl ToBase64 for [u8] {
     fn to_base64(&self, config: Config) -> String {
         encode::encode64_arch(self, config)
     }
+
+    fn try_to_base64(&self, config: Config) -> Result<String, ToBase64Error> {
+        encode::try_encode64_arch(self, config)
+    }
 }
 
-impl<'a, T: ?Sized + ToBase64> ToBase64 for &'a T {
+impl<T: ?Sized + ToBase64> ToBase64 for &T {
     fn to_base64(&self, config: Config) -> String {
         (**self).to_base64(config)
     }
+
+    fn try_to_base64(&self, config: Config) -> Result<String, ToBase64Error> {
+        (**self).try_to_base64(config)
+    }
+}
+
+#[doc(inline)]
+pub use encode::Error as ToBase64Error;
+
+pub use encode::Base64Encoder;
+#[cfg(feature = "std")]
+pub use encode::EncoderWriter;
+
+/// Returns the length of the base64 encoding of `input_len` bytes following
+/// the specified format configuration.
+///
+/// Panics if the length overflows `usize`.
+pub fn encoded_len(input_len: usize, config: Config) -> usize {
+    encode::encoded_len(input_len, config)
+}
+
+/// Converts `input` to a base64 value following the specified format
+/// configuration, writing it to the beginning of `output` without
+/// allocating. Returns the number of bytes written.
+///
+/// Returns `ToBase64Error::BufferTooSmall` if `output` is shorter than
+/// [`encoded_len`]`(input.len(), config)`.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::{encode_to_slice, STANDARD};
+///
+/// let mut buf = [0u8; 16];
+/// let len = encode_to_slice(b"Hello", STANDARD, &mut buf).unwrap();
+/// assert_eq!(&buf[..len], b"SGVsbG8=");
+/// ```
+pub fn encode_to_slice(
+    input: &[u8],
+    config: Config,
+    output: &mut [u8],
+) -> Result<usize, ToBase64Error> {
+    encode::encode64_to_slice_arch(input, config, output)
+}
+
+/// Converts `input` to a padded base64 value of exactly `M` characters,
+/// without allocating.
+///
+/// The output is a single line using the characters of `char_set`. `M` must
+/// be [`encoded_len`]`(N, config)` for a padded `config` without line
+/// wrapping, any other `M` fails to compile. Use [`encode_array_unpadded`]
+/// for unpadded output.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::{encode_array, Standard};
+///
+/// let output: [u8; 8] = encode_array(b"Hello", Standard);
+/// assert_eq!(&output, b"SGVsbG8=");
+/// ```
+///
+/// ```compile_fail
+/// let unpadded: [u8; 43] = b64_ct::encode_array(&[0u8; 32], b64_ct::Standard);
+/// ```
+pub fn encode_array<const N: usize, const M: usize>(
+    input: &[u8; N],
+    char_set: CharacterSet,
+) -> [u8; M] {
+    encode::encode64_to_array_arch::<N, M, true>(input, char_set)
+}
+
+/// Like [`encode_array`], but the output is unpadded.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::{encode_array_unpadded, Standard};
+///
+/// let output: [u8; 7] = encode_array_unpadded(b"Hello", Standard);
+/// assert_eq!(&output, b"SGVsbG8");
+/// ```
+///
+/// ```compile_fail
+/// let padded: [u8; 44] = b64_ct::encode_array_unpadded(&[0u8; 32], b64_ct::Standard);
+/// ```
+pub fn encode_array_unpadded<const N: usize, const M: usize>(
+    input: &[u8; N],
+    char_set: CharacterSet,
+) -> [u8; M] {
+    encode::encode64_to_array_arch::<N, M, false>(input, char_set)
 }
 
 #[doc(inline)]
 pub use decode::Error as FromBase64Error;
 
+/// Returns the length of the data encoded by the base64 value `input`.
+///
+/// Like [`decode_const`], this is a `const fn` intended for base64 literals,
+/// and panics if `input` isn't accepted by [`FromBase64::from_base64`].
+pub const fn decoded_len_const(input: &[u8]) -> usize {
+    decode::literal::decoded_len(input)
+}
+
+/// Converts `input`, interpreted as base64 encoded data, into exactly `N`
+/// bytes.
+///
+/// This is a `const fn`, so in a constant it decodes at compile time, where
+/// invalid input is a compile error. The accepted input is identical to that
+/// of [`FromBase64::from_base64`]. Panics if `input` isn't accepted or doesn't
+/// decode to exactly `N` bytes. The [`b64!`] macro determines `N`
+/// automatically.
+///
+/// Unlike the other decoding functions, this isn't resistant against
+/// side-channel attacks. Only use it for input that is known at compile time.
+///
+/// # Example
+///
+/// ```rust
+/// const NONCE: [u8; 5] = b64_ct::decode_const(b"SGVsbG8=");
+/// assert_eq!(&NONCE, b"Hello");
+/// ```
+pub const fn decode_const<const N: usize>(input: &[u8]) -> [u8; N] {
+    decode::literal::decode(input)
+}
+
+/// Decodes a base64 string literal at compile time into a byte array.
+///
+/// Invalid input is a compile error. See [`decode_const`] for details.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::b64;
+///
+/// const ROOT_KEY: [u8; 12] = b64!("SGVsbG8sIFdvcmxk");
+/// assert_eq!(&ROOT_KEY, b"Hello, World");
+/// ```
+///
+/// ```compile_fail
+/// const KEY: [u8; 3] = b64_ct::b64!("Zm9$");
+/// ```
+#[macro_export]
+macro_rules! b64 {
+    ($input:expr) => {{
+        const INPUT: &[u8] = $input.as_bytes();
+        const OUTPUT: [u8; $crate::decoded_len_const(INPUT)] = $crate::decode_const(INPUT);
+        OUTPUT
+    }};
+}
+
+pub use decode::Base64Decoder;
+#[cfg(feature = "std")]
+pub use decode::DecoderReader;
+
 /// A trait for converting from base64 encoded values.
+#[allow(clippy::wrong_self_convention)]
 pub trait FromBase64 {
     /// Converts the value of `self`, interpreted as base64 encoded data, into
     /// an owned vector of bytes, returning the vector.
     fn from_base64(&self) -> Result<Vec<u8>, FromBase64Error>;
+
+    /// Like [`from_base64`](FromBase64::from_base64), but returns
+    /// `FromBase64Error::AllocationFailed` instead of aborting if the output
+    /// can't be allocated.
+    ///
+    /// The default implementation calls `from_base64`.
+    fn try_from_base64(&self) -> Result<Vec<u8>, FromBase64Error> {
+        self.from_base64()
+    }
+
+    /// Like [`from_base64`](FromBase64::from_base64), but returns the
+    /// decoded bytes as [`SecretBytes`] that are wiped from memory when
+    /// dropped.
+    fn from_base64_secret(&self) -> Result<SecretBytes, FromBase64Error> {
+        self.from_base64().map(SecretBytes::new)
+    }
 }
 
 impl FromBase64 for str {
@@ -200,16 +514,129 @@ impl FromBase64 for str {
     fn from_base64(&self) -> Result<Vec<u8>, FromBase64Error> {
         self.as_bytes().from_base64()
     }
+
+    #[inline]
+    fn try_from_base64(&self) -> Result<Vec<u8>, FromBase64Error> {
+        self.as_bytes().try_from_base64()
+    }
 }
 
 impl FromBase64 for [u8] {
     fn from_base64(&self) -> Result<Vec<u8>, FromBase64Error> {
-        decode::decode64_arch(self)
+        decode::decode64_arch(self, DecodeConfig::default())
+    }
+
+    fn try_from_base64(&self) -> Result<Vec<u8>, FromBase64Error> {
+        decode::try_decode64_arch(self, DecodeConfig::default())
     }
 }
 
-impl<'a, T: ?Sized + FromBase64> FromBase64 for &'a T {
+impl<T: ?Sized + FromBase64> FromBase64 for &T {
     fn from_base64(&self) -> Result<Vec<u8>, FromBase64Error> {
         (**self).from_base64()
     }
+
+    fn try_from_base64(&self) -> Result<Vec<u8>, FromBase64Error> {
+        (**self).try_from_base64()
+    }
+}
+
+/// Converts `input`, interpreted as base64 encoded data following the
+/// specified decoding configuration, into an owned vector of bytes.
+///
+/// This is the configurable counterpart of [`FromBase64::from_base64`].
+///
+/// # Example
+///
+/// This rejects a base64url string when decoding standard base64.
+///
+/// ```rust
+/// use b64_ct::{decode_config, CharacterSet, DecodeConfig, FromBase64Error};
+///
+/// let config = DecodeConfig {
+///     char_set: Some(CharacterSet::Standard),
+///     ..Default::default()
+/// };
+/// assert_eq!(decode_config(b"-_8", config), Err(FromBase64Error::InvalidCharacter(0)));
+/// assert_eq!(decode_config(b"+/8", config), Ok(vec![251, 255]));
+/// ```
+pub fn decode_config(input: &[u8], config: DecodeConfig) -> Result<Vec<u8>, FromBase64Error> {
+    decode::decode64_arch(input, config)
+}
+
+/// Like [`decode_config`], but returns `FromBase64Error::AllocationFailed`
+/// instead of aborting if the output can't be allocated.
+pub fn try_decode_config(input: &[u8], config: DecodeConfig) -> Result<Vec<u8>, FromBase64Error> {
+    decode::try_decode64_arch(input, config)
+}
+
+/// Like [`decode_config`], but returns the decoded bytes as [`SecretBytes`]
+/// that are wiped from memory when dropped.
+pub fn decode_secret_config(
+    input: &[u8],
+    config: DecodeConfig,
+) -> Result<SecretBytes, FromBase64Error> {
+    decode_config(input, config).map(SecretBytes::new)
+}
+
+/// Converts `input`, interpreted as base64 encoded data, into bytes written
+/// to the beginning of `output` without allocating. Returns the number of
+/// bytes written.
+///
+/// Returns `FromBase64Error::BufferTooSmall` if the decoded data doesn't fit
+/// in `output`. A buffer of `input.len() / 4 * 3 + 2` bytes is always large
+/// enough. If an error is returned, any partially decoded data written to
+/// `output` has been wiped.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::decode_to_slice;
+///
+/// let mut buf = [0u8; 16];
+/// let len = decode_to_slice(b"SGVsbG8=", &mut buf).unwrap();
+/// assert_eq!(&buf[..len], b"Hello");
+/// ```
+pub fn decode_to_slice(input: &[u8], output: &mut [u8]) -> Result<usize, FromBase64Error> {
+    decode::decode64_to_slice_arch(input, DecodeConfig::default(), output)
+}
+
+/// Like [`decode_to_slice`], but following the specified decoding
+/// configuration.
+pub fn decode_to_slice_config(
+    input: &[u8],
+    config: DecodeConfig,
+    output: &mut [u8],
+) -> Result<usize, FromBase64Error> {
+    decode::decode64_to_slice_arch(input, config, output)
+}
+
+/// Converts `input`, interpreted as base64 encoded data, into an array of
+/// exactly `N` bytes without allocating.
+///
+/// Returns `FromBase64Error::WrongLength` if the input is valid, but the
+/// decoded data isn't exactly `N` bytes long. This check only depends on the
+/// length of the decoded data. Other errors take precedence, even if the
+/// input is too long. On error, the partially decoded data is wiped.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::{decode_array, FromBase64Error};
+///
+/// let nonce: [u8; 5] = decode_array(b"SGVsbG8=").unwrap();
+/// assert_eq!(&nonce, b"Hello");
+/// assert_eq!(decode_array::<4>(b"SGVsbG8="), Err(FromBase64Error::WrongLength));
+/// ```
+pub fn decode_array<const N: usize>(input: &[u8]) -> Result<[u8; N], FromBase64Error> {
+    decode::decode64_to_array_arch(input, DecodeConfig::default())
+}
+
+/// Like [`decode_array`], but following the specified decoding
+/// configuration.
+pub fn decode_array_config<const N: usize>(
+    input: &[u8],
+    config: DecodeConfig,
+) -> Result<[u8; N], FromBase64Error> {
+    decode::decode64_to_array_arch(input, config)
 }
@@ -4,7 +4,28 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-#[cfg(not(feature = "std"))]
+/// Without `std`, detect CPU features using CPUID.
+#[cfg(all(
+    not(feature = "std"),
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(target_env = "sgx")
+))]
+#[macro_export]
+macro_rules! is_x86_feature_detected {
+    ($feat:literal) => {
+        cfg!(target_feature = $feat) || $crate::__cpuid_is_detected($feat)
+    };
+}
+
+/// Without `std`, in SGX enclaves and on other architectures, only the
+/// features enabled at compile time are detected.
+#[cfg(all(
+    not(feature = "std"),
+    not(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        not(target_env = "sgx")
+    ))
+))]
 #[macro_export]
 macro_rules! is_x86_feature_detected {
     ($feat:literal) => {
@@ -12,10 +33,198 @@ macro_rules! is_x86_feature_detected {
     };
 }
 
+#[cfg(all(
+    not(feature = "std"),
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(target_env = "sgx")
+))]
+pub mod cpuid {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86::{__cpuid, __cpuid_count, _xgetbv, has_cpuid, CpuidResult};
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64::{__cpuid, __cpuid_count, _xgetbv, CpuidResult};
+
+    /// XCR0 bits for the SSE (XMM) and AVX (YMM) register state.
+    const XCR0_XMM_YMM: u64 = 0b110;
+
+    #[cfg(target_arch = "x86_64")]
+    fn has_cpuid() -> bool {
+        true
+    }
+
+    #[target_feature(enable = "xsave")]
+    unsafe fn xgetbv0() -> u64 {
+        _xgetbv(0)
+    }
+
+    fn bit(reg: u32, bit: u32) -> bool {
+        reg & (1 << bit) != 0
+    }
+
+    /// Returns whether `feature` is supported by the CPU and, for AVX
+    /// features, whether the OS saves the YMM registers on context switch.
+    ///
+    /// Only the features used by this crate are recognized.
+    #[allow(unused_unsafe)]
+    pub fn is_detected(feature: &str) -> bool {
+        if !has_cpuid() {
+            return false;
+        }
+
+        // SAFETY: CPUID is available, leaf 7 is only queried if supported.
+        let (leaf1, leaf7) = unsafe {
+            let max_leaf = __cpuid(0).eax;
+            let leaf7 = match max_leaf >= 7 {
+                true => __cpuid_count(7, 0),
+                false => CpuidResult {
+                    eax: 0,
+                    ebx: 0,
+                    ecx: 0,
+                    edx: 0,
+                },
+            };
+            (__cpuid(1), leaf7)
+        };
+
+        let avx = bit(leaf1.ecx, 28)
+            && bit(leaf1.ecx, 27) // OSXSAVE
+            // SAFETY: OSXSAVE means XGETBV is supported and enabled.
+            && unsafe { xgetbv0() } & XCR0_XMM_YMM == XCR0_XMM_YMM;
+
+        match feature {
+            "ssse3" => bit(leaf1.ecx, 9),
+            "sse4.1" => bit(leaf1.ecx, 19),
+            "sse4.2" => bit(leaf1.ecx, 20),
+            "popcnt" => bit(leaf1.ecx, 23),
+            "bmi1" => bit(leaf7.ebx, 3),
+            "avx2" => avx && bit(leaf7.ebx, 5),
+            _ => false,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        #[test]
+        fn same_as_std() {
+            use super::is_detected;
+
+            assert_eq!(is_detected("ssse3"), std::is_x86_feature_detected!("ssse3"));
+            assert_eq!(
+                is_detected("sse4.1"),
+                std::is_x86_feature_detected!("sse4.1")
+            );
+            assert_eq!(
+                is_detected("sse4.2"),
+                std::is_x86_feature_detected!("sse4.2")
+            );
+            assert_eq!(
+                is_detected("popcnt"),
+                std::is_x86_feature_detected!("popcnt")
+            );
+            assert_eq!(is_detected("bmi1"), std::is_x86_feature_detected!("bmi1"));
+            assert_eq!(is_detected("avx2"), std::is_x86_feature_detected!("avx2"));
+        }
+    }
+}
+
+/// Overwrite `buf` with zeros if the `zeroize` feature is enabled, in a way
+/// that isn't optimized away.
+#[inline(always)]
+pub(crate) fn wipe(buf: &mut [u8]) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(buf);
+    #[cfg(not(feature = "zeroize"))]
+    let _ = buf;
+}
+
+/// Overwrite `buf` with zeros in a way that isn't optimized away.
+///
+/// Unlike `wipe`, this always wipes, using `zeroize` if the feature is
+/// enabled. It is used for output buffers when decoding fails.
+pub(crate) fn wipe_output(buf: &mut [u8]) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(buf);
+    #[cfg(not(feature = "zeroize"))]
+    {
+        for b in buf {
+            // SAFETY: `b` is a valid, aligned reference.
+            unsafe { core::ptr::write_volatile(b, 0) };
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// Overwrite the initialized part and the spare capacity of `vec` with zeros
+/// and clear it, in a way that isn't optimized away.
+///
+/// Like `wipe_output`, this always wipes, using `zeroize` if the feature is
+/// enabled.
+pub(crate) fn wipe_vec(vec: &mut alloc::vec::Vec<u8>) {
+    #[cfg(feature = "zeroize")]
+    zeroize::Zeroize::zeroize(vec);
+    #[cfg(not(feature = "zeroize"))]
+    {
+        vec.clear();
+        for b in vec.spare_capacity_mut() {
+            // SAFETY: `b` is a valid, aligned pointer into the allocation.
+            unsafe { core::ptr::write_volatile(b.as_mut_ptr(), 0) };
+        }
+        core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// A buffer that is wiped when dropped, if the `zeroize` feature is enabled.
+///
+/// For a `Vec`, only the initialized part is wiped.
+pub(crate) struct Scratch<T: AsMut<[u8]>>(pub(crate) T);
+
+impl<T: AsMut<[u8]>> core::ops::Deref for Scratch<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: AsMut<[u8]>> core::ops::DerefMut for Scratch<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<T: AsMut<[u8]>> Drop for Scratch<T> {
+    fn drop(&mut self) {
+        wipe(self.0.as_mut())
+    }
+}
+
 pub(crate) const LUT_STANDARD: [u8; 64] =
     *b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
 #[inline(always)]
 pub(crate) fn div_roundup(numerator: usize, denominator: usize) -> usize {
-    (numerator + denominator - 1) / denominator
+    numerator.div_ceil(denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn wipe_vec() {
+        let mut vec = std::vec![0x55u8; 16];
+        vec.truncate(8);
+        super::wipe_vec(&mut vec);
+        assert!(vec.is_empty());
+        // SAFETY: the spare capacity was initialized by `wipe_vec`.
+        let wiped = unsafe { core::slice::from_raw_parts(vec.as_ptr(), 16) };
+        assert!(wiped.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn wipe_output() {
+        let mut buf = [0x55u8; 16];
+        super::wipe_output(&mut buf[..8]);
+        assert_eq!(buf[..8], [0; 8]);
+        assert_eq!(buf[8..], [0x55; 8]);
+    }
 }
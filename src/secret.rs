@@ -0,0 +1,211 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use crate::misc::wipe_vec;
+
+/// Bytes that are wiped from memory when dropped.
+///
+/// The bytes are only accessible through [`expose_secret`], and are redacted
+/// when formatted using `Debug`. This type doesn't implement `Clone`, so the
+/// bytes aren't copied accidentally.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::FromBase64;
+///
+/// let key = "c2VjcmV0".from_base64_secret().unwrap();
+/// assert_eq!(format!("{:?}", key), "SecretBytes([REDACTED])");
+/// assert_eq!(key.expose_secret(), b"secret");
+/// ```
+///
+/// [`expose_secret`]: SecretBytes::expose_secret
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    /// Take ownership of `bytes`.
+    pub fn new(bytes: Vec<u8>) -> SecretBytes {
+        SecretBytes(bytes)
+    }
+
+    /// Get a reference to the secret bytes.
+    pub fn expose_secret(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Get a mutable reference to the secret bytes.
+    pub fn expose_secret_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    /// The number of bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there are no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> SecretBytes {
+        SecretBytes::new(bytes)
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        wipe_vec(&mut self.0)
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretBytes([REDACTED])")
+    }
+}
+
+/// A string that is wiped from memory when dropped.
+///
+/// The string is only accessible through [`expose_secret`], and is redacted
+/// when formatted using `Debug`. This type doesn't implement `Clone` or
+/// `Display`, so the string isn't copied or printed accidentally.
+///
+/// # Example
+///
+/// ```rust
+/// use b64_ct::{ToBase64, STANDARD};
+///
+/// let key = b"secret".to_base64_secret(STANDARD);
+/// assert_eq!(format!("{:?}", key), "SecretString([REDACTED])");
+/// assert_eq!(key.expose_secret(), "c2VjcmV0");
+/// ```
+///
+/// [`expose_secret`]: SecretString::expose_secret
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Take ownership of `string`.
+    pub fn new(string: String) -> SecretString {
+        SecretString(string)
+    }
+
+    /// Get a reference to the secret string.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+
+    /// The length of the string in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the string is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(string: String) -> SecretString {
+        SecretString::new(string)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        // SAFETY: the string is cleared, so it remains valid UTF-8.
+        wipe_vec(unsafe { self.0.as_mut_vec() })
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for SecretBytes {
+    fn zeroize(&mut self) {
+        wipe_vec(&mut self.0)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for SecretBytes {}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for SecretString {
+    fn zeroize(&mut self) {
+        // SAFETY: the string is cleared, so it remains valid UTF-8.
+        wipe_vec(unsafe { self.0.as_mut_vec() })
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for SecretString {}
+
+#[cfg(feature = "secrecy")]
+impl secrecy::ExposeSecret<[u8]> for SecretBytes {
+    fn expose_secret(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+#[cfg(feature = "secrecy")]
+impl secrecy::ExposeSecret<str> for SecretString {
+    fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+/// The bytes are copied into an allocation of the exact length, instead of
+/// shrinking the vector, which could leave a copy behind.
+#[cfg(feature = "secrecy")]
+impl From<SecretBytes> for secrecy::SecretSlice<u8> {
+    fn from(secret: SecretBytes) -> secrecy::SecretSlice<u8> {
+        secrecy::SecretBox::new(alloc::boxed::Box::from(secret.expose_secret()))
+    }
+}
+
+/// The string is copied into an allocation of the exact length, instead of
+/// shrinking the string, which could leave a copy behind.
+#[cfg(feature = "secrecy")]
+impl From<SecretString> for secrecy::SecretString {
+    fn from(secret: SecretString) -> secrecy::SecretString {
+        secrecy::SecretBox::new(alloc::boxed::Box::from(secret.expose_secret()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted() {
+        let bytes = SecretBytes::new(b"secret".to_vec());
+        assert_eq!(std::format!("{:?}", bytes), "SecretBytes([REDACTED])");
+        let string = SecretString::new("secret".into());
+        assert_eq!(std::format!("{:?}", string), "SecretString([REDACTED])");
+    }
+
+    #[test]
+    #[cfg(feature = "secrecy")]
+    fn secrecy() {
+        use secrecy::ExposeSecret;
+
+        let bytes = secrecy::SecretSlice::from(SecretBytes::new(b"secret".to_vec()));
+        assert_eq!(bytes.expose_secret(), b"secret");
+        let string = secrecy::SecretString::from(SecretString::new("secret".into()));
+        assert_eq!(string.expose_secret(), "secret");
+    }
+}
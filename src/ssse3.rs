@@ -0,0 +1,26 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#[cfg(target_arch = "x86")]
+use core::arch::x86::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+pub(crate) unsafe fn mm_setr_epu8(e: [u8; 16]) -> __m128i {
+    core::mem::transmute(e)
+}
+
+pub(crate) unsafe fn _mm_not_si128(i: __m128i) -> __m128i {
+    _mm_xor_si128(i, _mm_set1_epi8(!0))
+}
+
+pub(crate) unsafe fn array_as_m128i(v: [u8; 16]) -> __m128i {
+    core::mem::transmute(v)
+}
+
+pub(crate) unsafe fn m128i_as_array(v: __m128i) -> [u8; 16] {
+    core::mem::transmute(v)
+}
@@ -0,0 +1,62 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Branch-free operations on the 8 bytes of a `u64`. Masks have all bits of
+//! a byte set if the condition holds for that byte and none otherwise.
+
+pub(crate) const ONES: u64 = 0x0101_0101_0101_0101;
+const HIGH: u64 = 0x8080_8080_8080_8080;
+
+/// Repeat `b` in every byte.
+#[inline(always)]
+pub(crate) const fn splat(b: u8) -> u64 {
+    ONES * b as u64
+}
+
+/// Bytewise wrapping addition.
+#[inline(always)]
+pub(crate) fn add(a: u64, b: u64) -> u64 {
+    ((a & !HIGH) + (b & !HIGH)) ^ ((a ^ b) & HIGH)
+}
+
+/// Turn a mask of the high bits of each byte into a full byte mask.
+#[inline(always)]
+fn widen(high: u64) -> u64 {
+    ((high & HIGH) >> 7) * 0xff
+}
+
+/// `lo <= a && a <= hi` for each byte of `a`. `lo` and `hi` must be ASCII.
+#[inline(always)]
+pub(crate) fn in_range(a: u64, lo: u8, hi: u8) -> u64 {
+    let ascii = !a;
+    let low = a & !HIGH;
+    // The high bit is set iff `low >= lo`, resp. `low > hi`. These additions
+    // can't carry into the next byte.
+    let ge_lo = low + splat(0x80 - lo);
+    let gt_hi = low + splat(0x7f - hi);
+    widen(ascii & ge_lo & !gt_hi)
+}
+
+/// `a == b` for each byte of `a`. `b` must be ASCII.
+#[inline(always)]
+pub(crate) fn eq(a: u64, b: u8) -> u64 {
+    in_range(a, b, b)
+}
+
+/// `a > b` for each byte of `a`. All bytes of `a` and `b` must be ASCII.
+#[inline(always)]
+pub(crate) fn gt(a: u64, b: u8) -> u64 {
+    widen((a | HIGH) - splat(b + 1))
+}
+
+/// Collect the lowest bit of every byte of the mask into an integer, with
+/// bit `i` corresponding to byte `i`.
+#[inline(always)]
+pub(crate) fn to_bitmask(mask: u64) -> u32 {
+    // Every bit ends up in a different position of the top byte, so there
+    // are no carries.
+    ((mask & ONES).wrapping_mul(0x0102_0408_1020_4080) >> 56) as u32
+}
@@ -0,0 +1,64 @@
+/* Copyright (c) Fortanix, Inc.
+ *
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Checks that the `try_` functions report allocation failures instead of
+//! aborting. This is a separate test binary because it replaces the global
+//! allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use b64_ct::{
+    try_decode_config, DecodeConfig, FromBase64, FromBase64Error, ToBase64, ToBase64Error, STANDARD,
+};
+
+thread_local! {
+    static FAIL: Cell<bool> = const { Cell::new(false) };
+}
+
+struct FailingAllocator;
+
+unsafe impl GlobalAlloc for FailingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if FAIL.with(Cell::get) {
+            return std::ptr::null_mut();
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: FailingAllocator = FailingAllocator;
+
+/// Runs `f` with all allocations on this thread failing.
+fn failing<T>(f: impl FnOnce() -> T) -> T {
+    FAIL.with(|fail| fail.set(true));
+    let result = f();
+    FAIL.with(|fail| fail.set(false));
+    result
+}
+
+#[test]
+fn encode() {
+    let result = failing(|| b"foobar".try_to_base64(STANDARD));
+    assert_eq!(result, Err(ToBase64Error::AllocationFailed));
+    assert_eq!(b"foobar".try_to_base64(STANDARD).unwrap(), "Zm9vYmFy");
+}
+
+#[test]
+fn decode() {
+    let result = failing(|| "Zm9vYmFy".try_from_base64());
+    assert_eq!(result, Err(FromBase64Error::AllocationFailed));
+
+    let result = failing(|| try_decode_config(b"Zm9vYmFy", DecodeConfig::default()));
+    assert_eq!(result, Err(FromBase64Error::AllocationFailed));
+
+    assert_eq!("Zm9vYmFy".try_from_base64().unwrap(), b"foobar");
+}